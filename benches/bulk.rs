@@ -0,0 +1,138 @@
+//! Compare bulk `create` of new and existing values and `get` throughput
+//! against re-preparing each statement per call, as `Normal` did before
+//! caching statements, and bulk pair inserts against inserting one pair at
+//! a time.
+//!
+//! Run with `cargo bench`.
+
+use normal::{IdPairs, Normal};
+use std::time::{Duration, Instant};
+
+const COUNT: i64 = 20_000;
+
+fn keyword(i: i64) -> String {
+    format!("keyword-{}", i)
+}
+
+/// Report operations per second for a timed run.
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<24} {:>10.0} ops/s ({:?})",
+        name,
+        COUNT as f64 / elapsed.as_secs_f64(),
+        elapsed
+    );
+}
+
+fn bench_cached() {
+    let norm = Normal::new(":memory:", "keywords", "keyword").unwrap();
+
+    let start = Instant::now();
+    for i in 0..COUNT {
+        norm.create(&keyword(i)).unwrap();
+    }
+    report("cached create", start.elapsed());
+
+    let start = Instant::now();
+    for i in 0..COUNT {
+        norm.create(&keyword(i)).unwrap();
+    }
+    report("cached create existing", start.elapsed());
+
+    let start = Instant::now();
+    for i in 1..=COUNT {
+        norm.get(i).unwrap();
+    }
+    report("cached get", start.elapsed());
+}
+
+/// Queries `Normal::create_with_status` and `Normal::get` run on the table
+/// it creates.
+const INSERT: &str = "
+    INSERT INTO \"keywords\" (\"keyword\") SELECT ?1
+    WHERE NOT EXISTS (SELECT 1 FROM \"keywords\" WHERE \"keyword\" = ?1)
+        AND NOT EXISTS (SELECT 1 FROM \"keywords_aliases\" WHERE alias = ?1)
+    ON CONFLICT DO NOTHING RETURNING rowid;
+";
+const FIND: &str = "
+    SELECT rowid FROM \"keywords\" WHERE \"keyword\" = ?1
+    UNION ALL SELECT id FROM \"keywords_aliases\" WHERE alias = ?1
+    LIMIT 1
+";
+const GET: &str = "SELECT \"keyword\" FROM \"keywords\" WHERE rowid = ?";
+
+/// Open an in-memory database with the schema `Normal` creates.
+fn normal_schema() -> sqlite::Connection {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let path = tmpdir.path().join("schema.sqlite3");
+    Normal::new(path.to_str().unwrap(), "keywords", "keyword").unwrap();
+    let source = sqlite::open(&path).unwrap();
+    let conn = sqlite::open(":memory:").unwrap();
+    let mut schema = source
+        .prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'")
+        .unwrap();
+    while schema.next().unwrap() == sqlite::State::Row {
+        conn.execute(schema.read::<String>(0).unwrap()).unwrap();
+    }
+    conn
+}
+
+/// Create a value as `Normal::create` does, re-preparing each statement.
+fn create_uncached(conn: &sqlite::Connection, value: &str) -> i64 {
+    let mut statement = conn.prepare(INSERT).unwrap();
+    statement.bind(1, value).unwrap();
+    if statement.next().unwrap() == sqlite::State::Row {
+        return statement.read::<i64>(0).unwrap();
+    }
+    let mut statement = conn.prepare(FIND).unwrap();
+    statement.bind(1, value).unwrap();
+    statement.next().unwrap();
+    statement.read::<i64>(0).unwrap()
+}
+
+fn bench_uncached() {
+    let conn = normal_schema();
+
+    let start = Instant::now();
+    for i in 0..COUNT {
+        create_uncached(&conn, &keyword(i));
+    }
+    report("uncached create", start.elapsed());
+
+    let start = Instant::now();
+    for i in 0..COUNT {
+        create_uncached(&conn, &keyword(i));
+    }
+    report("uncached create existing", start.elapsed());
+
+    let start = Instant::now();
+    for i in 1..=COUNT {
+        let mut statement = conn.prepare(GET).unwrap();
+        statement.bind(1, i).unwrap();
+        statement.next().unwrap();
+        statement.read::<String>(0).unwrap();
+    }
+    report("uncached get", start.elapsed());
+}
+
+fn bench_pairs() {
+    let pairs = IdPairs::new(":memory:", "doc_keywords", "doc", "keyword").unwrap();
+
+    let start = Instant::now();
+    for i in 0..COUNT {
+        pairs.insert(i, i % 100).unwrap();
+    }
+    report("pairs insert", start.elapsed());
+
+    let start = Instant::now();
+    pairs
+        .insert_bulk((COUNT..2 * COUNT).map(|i| (i, i % 100)))
+        .unwrap();
+    report("pairs insert_bulk", start.elapsed());
+}
+
+fn main() {
+    bench_uncached();
+    bench_cached();
+    bench_pairs();
+}
@@ -0,0 +1,621 @@
+use crate::{
+    CreateStatus, Cursor, FromSql, IdPairs, InsertCounts, Normal, NormalError, OnDelete, Page,
+    PairColumn, Record, SearchMode, ToSql,
+};
+use futures_channel::{mpsc, oneshot};
+use futures_core::Stream;
+use futures_executor::block_on;
+use parking_lot::Mutex;
+use sqlite::Value;
+use std::future::poll_fn;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc as sync_mpsc, Arc};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Number of streamed items buffered ahead of the consumer.
+const STREAM_BUFFER: usize = 64;
+
+/// Dedicated threads running blocking SQLite calls for async callers.
+///
+/// The threads exit once every handle to them is dropped.
+#[derive(Clone)]
+struct Worker {
+    jobs: sync_mpsc::Sender<Job>,
+}
+
+impl Worker {
+    /// Start the threads.  Threads already started exit if a later one
+    /// cannot be.
+    fn spawn(threads: usize) -> Result<Worker, NormalError> {
+        let (jobs, queue) = sync_mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..threads.max(1) {
+            let queue = queue.clone();
+            thread::Builder::new()
+                .name("normal-worker".to_string())
+                .spawn(move || loop {
+                    let job = match queue.lock().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    // A panicking call drops its reply channel; keep serving others.
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                })
+                .map_err(|e| {
+                    NormalError::WorkerFailed(format!("cannot spawn worker thread: {}", e))
+                })?;
+        }
+        Ok(Worker { jobs })
+    }
+
+    /// Run `f` on a worker thread and wait for its result.
+    async fn call<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> Result<T, NormalError> + Send + 'static,
+    ) -> Result<T, NormalError> {
+        let (reply, result) = oneshot::channel();
+        self.submit(Box::new(move || {
+            let _ = reply.send(f()); // the caller may have stopped waiting.
+        }));
+        result.await.unwrap_or_else(|_| {
+            Err(NormalError::WorkerFailed(
+                "worker thread panicked during an async call".to_string(),
+            ))
+        })
+    }
+
+    /// Run `f` on a worker thread, streaming the items it sends.  The
+    /// thread waits while `STREAM_BUFFER` items are unread, so results are
+    /// not read ahead of a slow consumer.
+    fn stream<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut mpsc::Sender<T>) + Send + 'static,
+    ) -> impl Stream<Item = T> {
+        let (mut items, stream) = mpsc::channel(STREAM_BUFFER);
+        self.submit(Box::new(move || f(&mut items)));
+        stream
+    }
+
+    fn submit(&self, job: Job) {
+        // The threads outlive every handle, so the job is always queued.
+        let _ = self.jobs.send(job);
+    }
+}
+
+/// Send an item, waiting for room in the buffer.  Returns whether the
+/// receiver is still there.
+fn send_blocking<T>(items: &mut mpsc::Sender<T>, item: T) -> bool {
+    block_on(poll_fn(|cx| items.poll_ready(cx))).is_ok() && items.start_send(item).is_ok()
+}
+
+/// Send search results until the receiver goes away.
+fn forward<T>(
+    items: &mut mpsc::Sender<Result<T, NormalError>>,
+    results: Result<impl Iterator<Item = Result<T, NormalError>>, NormalError>,
+) {
+    match results {
+        Ok(results) => {
+            for result in results {
+                if !send_blocking(items, result) {
+                    return;
+                }
+            }
+        }
+        Err(e) => {
+            send_blocking(items, Err(e));
+        }
+    }
+}
+
+/// Async access to a normalization table.
+///
+/// Calls run on dedicated worker threads, so they never block the executor.
+/// A single thread runs calls one at a time; serve the table from more
+/// threads to run them concurrently, e.g. with the readers of
+/// `Database::open_pool`.  Clones share the threads.  A stream occupies its
+/// thread until it is read to the end or dropped.
+#[derive(Clone)]
+pub struct AsyncNormal {
+    normal: Arc<Normal>,
+    worker: Worker,
+}
+
+impl AsyncNormal {
+    /// Serve a table from its own worker thread.
+    pub fn new(normal: Normal) -> Result<AsyncNormal, NormalError> {
+        AsyncNormal::new_with_threads(normal, 1)
+    }
+
+    /// Serve a table from `threads` worker threads.
+    pub fn new_with_threads(normal: Normal, threads: usize) -> Result<AsyncNormal, NormalError> {
+        Ok(AsyncNormal {
+            normal: Arc::new(normal),
+            worker: Worker::spawn(threads)?,
+        })
+    }
+
+    /// Insert a new keyword/token and return the associated id.
+    pub async fn create(&self, value: &str) -> Result<i64, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker.call(move || normal.create(&value)).await
+    }
+
+    /// Insert a new keyword/token and return the associated id, along with
+    /// whether the value was newly inserted.
+    pub async fn create_with_status(
+        &self,
+        value: &str,
+    ) -> Result<(i64, CreateStatus), NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .call(move || normal.create_with_status(&value))
+            .await
+    }
+
+    /// Insert keywords/tokens in one transaction and return their ids in
+    /// order.  On error, none of the values are inserted.
+    pub async fn create_bulk(&self, values: Vec<String>) -> Result<Vec<i64>, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.create_bulk(values)).await
+    }
+
+    /// Find the id of an existing value without inserting it.
+    pub async fn lookup(&self, value: &str) -> Result<Option<i64>, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker.call(move || normal.lookup(&value)).await
+    }
+
+    /// Find the ids of existing values in order, with `None` for values
+    /// not in the table.
+    pub async fn lookup_bulk(&self, values: Vec<String>) -> Result<Vec<Option<i64>>, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.lookup_bulk(&values)).await
+    }
+
+    /// Return up to `limit` keys spelled similarly to the value, as
+    /// `(id, key, score)`, best first.  Requires `Normal::enable_fuzzy`.
+    pub async fn lookup_fuzzy(
+        &self,
+        value: &str,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(i64, String, f64)>, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .call(move || normal.lookup_fuzzy(&value, threshold, limit))
+            .await
+    }
+
+    /// Register another spelling for the value with the id.
+    pub async fn add_alias(&self, id: i64, alias: &str) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        let alias = alias.to_string();
+        self.worker.call(move || normal.add_alias(id, &alias)).await
+    }
+
+    /// Remove an alias, returning whether it existed.
+    pub async fn remove_alias(&self, alias: &str) -> Result<bool, NormalError> {
+        let normal = self.normal.clone();
+        let alias = alias.to_string();
+        self.worker.call(move || normal.remove_alias(&alias)).await
+    }
+
+    /// List the aliases of the value with the id.
+    pub async fn aliases(&self, id: i64) -> Result<Vec<String>, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.aliases(id)).await
+    }
+
+    /// Merge a duplicate value into another, rewriting registered pairs.
+    pub async fn merge(&self, winner: i64, loser: i64) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.merge(winner, loser)).await
+    }
+
+    /// Turn an alias back into a value of its own, returning the new id.
+    pub async fn split(&self, alias: &str) -> Result<i64, NormalError> {
+        let normal = self.normal.clone();
+        let alias = alias.to_string();
+        self.worker.call(move || normal.split(&alias)).await
+    }
+
+    /// Change the spelling of the value with the id.
+    pub async fn rename(&self, id: i64, new_value: &str) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        let new_value = new_value.to_string();
+        self.worker
+            .call(move || normal.rename(id, &new_value))
+            .await
+    }
+
+    /// Delete the value with the id along with its aliases.
+    pub async fn delete(&self, id: i64, on_delete: OnDelete) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.delete(id, on_delete)).await
+    }
+
+    /// Retrieve the keyword/token with the id.
+    pub async fn get(&self, id: i64) -> Result<String, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.get(id)).await
+    }
+
+    /// Retrieve the spelling of the keyword/token with the id as first
+    /// created, if the table keeps a display column.
+    pub async fn get_display(&self, id: i64) -> Result<String, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.get_display(id)).await
+    }
+
+    /// Retrieve the values for multiple indices in order, with `None` for
+    /// missing ids.
+    pub async fn get_bulk(&self, ids: Vec<i64>) -> Result<Vec<Option<String>>, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.get_bulk(&ids)).await
+    }
+
+    /// Compute the non-key/notation column names.
+    pub async fn get_nonkeys(&self) -> Result<Vec<String>, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.get_nonkeys()).await
+    }
+
+    /// Get the value from a non-key column, e.g. as `String`, `i64`, `f64`,
+    /// `bool` or `Vec<u8>`.  Read `Option<T>` to accept NULL.
+    pub async fn get_nonkey<T: FromSql + Send + 'static>(
+        &self,
+        id: i64,
+        column_name: &str,
+    ) -> Result<T, NormalError> {
+        let normal = self.normal.clone();
+        let column_name = column_name.to_string();
+        self.worker
+            .call(move || normal.get_nonkey(id, &column_name))
+            .await
+    }
+
+    /// Associate a non-key value with a row.
+    pub async fn notate<T: ToSql + Send + 'static>(
+        &self,
+        id: i64,
+        column_name: &str,
+        note: T,
+    ) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        let column_name = column_name.to_string();
+        self.worker
+            .call(move || normal.notate(id, &column_name, note))
+            .await
+    }
+
+    /// Get the key and every non-key value of a row in one query.
+    pub async fn get_record(&self, id: i64) -> Result<Record, NormalError> {
+        let normal = self.normal.clone();
+        self.worker.call(move || normal.get_record(id)).await
+    }
+
+    /// Associate several non-key values with a row in one statement.
+    pub async fn notate_many(
+        &self,
+        id: i64,
+        notes: Vec<(String, Value)>,
+    ) -> Result<(), NormalError> {
+        let normal = self.normal.clone();
+        self.worker
+            .call(move || normal.notate_many(id, &notes))
+            .await
+    }
+
+    /// Stream the ids and tokens matching the search string.
+    /// Use of SQL '%' wildcards is acceptable, and enables substring search.
+    /// Failures are yielded as errors and end the stream.
+    pub fn search(&self, value: &str) -> impl Stream<Item = Result<(i64, String), NormalError>> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .stream(move |items| forward(items, normal.search(&value)))
+    }
+
+    /// Stream the ids and tokens matching the search string in a mode.
+    pub fn search_with_mode(
+        &self,
+        value: &str,
+        mode: SearchMode,
+    ) -> impl Stream<Item = Result<(i64, String), NormalError>> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .stream(move |items| forward(items, normal.search_with_mode(&value, mode)))
+    }
+
+    /// Stream the ids and tokens matching the search string either directly
+    /// or through one of their aliases.
+    pub fn search_with_aliases(
+        &self,
+        value: &str,
+    ) -> impl Stream<Item = Result<(i64, String), NormalError>> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .stream(move |items| forward(items, normal.search_with_aliases(&value)))
+    }
+
+    /// Stream the ids and tokens matching a full-text query, best first.
+    /// Requires `Normal::enable_fts`.
+    pub fn search_fts(
+        &self,
+        query: &str,
+    ) -> impl Stream<Item = Result<(i64, String), NormalError>> {
+        let normal = self.normal.clone();
+        let query = query.to_string();
+        self.worker
+            .stream(move |items| forward(items, normal.search_fts(&query)))
+    }
+
+    /// Return up to `limit` tokens matching the search string with ids
+    /// after `last_idx`.
+    pub async fn search_page(
+        &self,
+        value: &str,
+        last_idx: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, String)>, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .call(move || {
+                page(limit, (0, String::new()), |dest| {
+                    normal.search_page(&value, last_idx, dest)
+                })
+            })
+            .await
+    }
+
+    /// Return a page of up to `limit` tokens matching the search string in
+    /// a mode, starting after the cursor.
+    pub async fn search_paged(
+        &self,
+        value: &str,
+        mode: SearchMode,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page<(i64, String)>, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .call(move || normal.search_paged(&value, mode, cursor.as_ref(), limit))
+            .await
+    }
+
+    /// Return up to `limit` tokens matching the search string in a mode
+    /// with ids after `last_idx`.
+    pub async fn search_page_with_mode(
+        &self,
+        value: &str,
+        mode: SearchMode,
+        last_idx: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, String)>, NormalError> {
+        let normal = self.normal.clone();
+        let value = value.to_string();
+        self.worker
+            .call(move || {
+                page(limit, (0, String::new()), |dest| {
+                    normal.search_page_with_mode(&value, mode, last_idx, dest)
+                })
+            })
+            .await
+    }
+}
+
+/// Async access to a pairs table.
+///
+/// Calls run on dedicated worker threads, so they never block the executor.
+/// A single thread runs calls one at a time; serve the table from more
+/// threads to run them concurrently, e.g. with the readers of
+/// `Database::open_pool`.  Clones share the threads.  A stream occupies its
+/// thread until it is read to the end or dropped.
+#[derive(Clone)]
+pub struct AsyncIdPairs {
+    pairs: Arc<IdPairs>,
+    worker: Worker,
+}
+
+impl AsyncIdPairs {
+    /// Serve a table from its own worker thread.
+    pub fn new(pairs: IdPairs) -> Result<AsyncIdPairs, NormalError> {
+        AsyncIdPairs::new_with_threads(pairs, 1)
+    }
+
+    /// Serve a table from `threads` worker threads.
+    pub fn new_with_threads(pairs: IdPairs, threads: usize) -> Result<AsyncIdPairs, NormalError> {
+        Ok(AsyncIdPairs {
+            pairs: Arc::new(pairs),
+            worker: Worker::spawn(threads)?,
+        })
+    }
+
+    /// Stream the values associated with a key.
+    /// Failures are yielded as errors and end the stream.
+    pub fn get(&self, key: i64) -> impl Stream<Item = Result<i64, NormalError>> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .stream(move |items| forward(items, pairs.get(key)))
+    }
+
+    /// Return up to `limit` values associated with a key, greater than
+    /// `min_val`.
+    pub async fn get_page(
+        &self,
+        key: i64,
+        min_val: i64,
+        limit: usize,
+    ) -> Result<Vec<i64>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || page(limit, 0, |dest| pairs.get_page(key, min_val, dest)))
+            .await
+    }
+
+    /// Insert a new key-value pair.
+    pub async fn insert(&self, key: i64, val: i64) -> Result<(), NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker.call(move || pairs.insert(key, val)).await
+    }
+
+    /// Insert key-value pairs in one transaction.
+    pub async fn insert_bulk(
+        &self,
+        new_pairs: Vec<(i64, i64)>,
+    ) -> Result<InsertCounts, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker.call(move || pairs.insert_bulk(new_pairs)).await
+    }
+
+    /// Remove a key-value pair, returning the number of pairs removed.
+    pub async fn remove(&self, key: i64, val: i64) -> Result<usize, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker.call(move || pairs.remove(key, val)).await
+    }
+
+    /// Remove every pair with the key, returning the number removed.
+    pub async fn remove_left(&self, key: i64) -> Result<usize, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker.call(move || pairs.remove_left(key)).await
+    }
+
+    /// Remove every pair with the value, returning the number removed.
+    pub async fn remove_right(&self, val: i64) -> Result<usize, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker.call(move || pairs.remove_right(val)).await
+    }
+
+    /// Atomically replace the values associated with a key, returning the
+    /// number of pairs removed or inserted.
+    pub async fn replace_left(&self, key: i64, new_vals: Vec<i64>) -> Result<usize, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || pairs.replace_left(key, new_vals))
+            .await
+    }
+
+    /// Stream the keys associated with a value.
+    /// Failures are yielded as errors and end the stream.
+    pub fn invert(&self, val: i64) -> impl Stream<Item = Result<i64, NormalError>> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .stream(move |items| forward(items, pairs.invert(val)))
+    }
+
+    /// Return up to `limit` keys associated with a value, greater than
+    /// `min_key`.
+    pub async fn invert_page(
+        &self,
+        value: i64,
+        min_key: i64,
+        limit: usize,
+    ) -> Result<Vec<i64>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || page(limit, 0, |dest| pairs.invert_page(value, min_key, dest)))
+            .await
+    }
+
+    /// Return up to `limit` pairs with left keys in `[min_key, max_key)`.
+    pub async fn page_left(
+        &self,
+        min_key: i64,
+        max_key: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, i64)>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || {
+                page(limit, (0, 0), |dest| {
+                    pairs.page_left(min_key, max_key, dest)
+                })
+            })
+            .await
+    }
+
+    /// Return up to `limit` pairs with right keys in `[min_key, max_key)`.
+    pub async fn page_right(
+        &self,
+        min_key: i64,
+        max_key: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, i64)>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || {
+                page(limit, (0, 0), |dest| {
+                    pairs.page_right(min_key, max_key, dest)
+                })
+            })
+            .await
+    }
+
+    /// Return a page of up to `limit` values associated with a key,
+    /// starting after the cursor.
+    pub async fn get_paged(
+        &self,
+        key: i64,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page<i64>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || pairs.get_paged(key, cursor.as_ref(), limit))
+            .await
+    }
+
+    /// Return a page of up to `limit` keys associated with a value,
+    /// starting after the cursor.
+    pub async fn invert_paged(
+        &self,
+        val: i64,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page<i64>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || pairs.invert_paged(val, cursor.as_ref(), limit))
+            .await
+    }
+
+    /// Return a page of up to `limit` pairs ordered by the column and then
+    /// the other, starting after the cursor.
+    pub async fn pairs_paged(
+        &self,
+        order: PairColumn,
+        cursor: Option<Cursor>,
+        limit: usize,
+    ) -> Result<Page<(i64, i64)>, NormalError> {
+        let pairs = self.pairs.clone();
+        self.worker
+            .call(move || pairs.pairs_paged(order, cursor.as_ref(), limit))
+            .await
+    }
+}
+
+/// Fill a page of up to `limit` items, keeping only those written.
+fn page<T: Clone>(
+    limit: usize,
+    empty: T,
+    fill: impl FnOnce(&mut [T]) -> Result<usize, NormalError>,
+) -> Result<Vec<T>, NormalError> {
+    let mut dest = vec![empty; limit];
+    let count = fill(&mut dest)?;
+    dest.truncate(count);
+    Ok(dest)
+}
+
+#[cfg(test)]
+#[path = "./async_model_test.rs"]
+mod async_model_test;
@@ -0,0 +1,285 @@
+use super::*;
+use crate::{ColumnType, Database, NonKeyColumn, PairColumn};
+use futures::executor::block_on;
+use futures::StreamExt;
+
+fn new_normal() -> AsyncNormal {
+    AsyncNormal::new(Normal::new(":memory:", "genres", "genre").unwrap()).unwrap()
+}
+
+fn new_pairs() -> AsyncIdPairs {
+    AsyncIdPairs::new(IdPairs::new(":memory:", "xys", "x", "y").unwrap()).unwrap()
+}
+
+/// Values created asynchronously are retrieved asynchronously.
+#[test]
+fn it_creates_and_gets() {
+    let norm = new_normal();
+    block_on(async {
+        assert_eq!(norm.create("blues").await.unwrap(), 1);
+        assert_eq!(
+            norm.create_with_status("blues").await.unwrap(),
+            (1, CreateStatus::Existing)
+        );
+        assert_eq!(
+            norm.create_bulk(vec!["jazz".to_string(), "punk".to_string()])
+                .await
+                .unwrap(),
+            vec![2, 3]
+        );
+        assert_eq!(norm.get(2).await.unwrap(), "jazz");
+        assert_eq!(norm.lookup("punk").await.unwrap(), Some(3));
+        assert_eq!(
+            norm.lookup_bulk(vec!["rap".to_string(), "blues".to_string()])
+                .await
+                .unwrap(),
+            vec![None, Some(1)]
+        );
+        assert!(matches!(norm.get(9).await, Err(NormalError::NotFound(_))));
+        assert_eq!(
+            norm.get_bulk(vec![3, 9, 1]).await.unwrap(),
+            vec![Some("punk".to_string()), None, Some("blues".to_string())]
+        );
+    });
+}
+
+/// Typed non-key values are written and read asynchronously.
+#[test]
+fn it_notates() {
+    let normal = Normal::new_with_typed_nonkeys(
+        ":memory:",
+        "genres",
+        "genre",
+        vec![NonKeyColumn::new("plays", ColumnType::Integer)],
+    )
+    .unwrap();
+    let norm = AsyncNormal::new(normal).unwrap();
+    block_on(async {
+        let id = norm.create("blues").await.unwrap();
+        norm.notate(id, "plays", 12i64).await.unwrap();
+        assert_eq!(norm.get_nonkey::<i64>(id, "plays").await.unwrap(), 12);
+        assert!(matches!(
+            norm.get_nonkey::<String>(id, "plays").await,
+            Err(NormalError::TypeMismatch { .. })
+        ));
+        norm.notate_many(id, vec![("plays".to_string(), Value::Integer(13))])
+            .await
+            .unwrap();
+        let record = norm.get_record(id).await.unwrap();
+        assert_eq!(record.key, "blues");
+        assert_eq!(record.get::<i64>("plays").unwrap(), 13);
+    });
+}
+
+/// Search results are streamed and paged.
+#[test]
+fn it_streams_search_results() {
+    let norm = new_normal();
+    block_on(async {
+        for genre in ["blues", "jazz", "bluegrass"].iter() {
+            norm.create(genre).await.unwrap();
+        }
+        let found: Vec<_> = norm.search("blue%").collect().await;
+        let found = found
+            .into_iter()
+            .collect::<Result<Vec<_>, NormalError>>()
+            .unwrap();
+        assert_eq!(
+            found,
+            vec![(1, "blues".to_string()), (3, "bluegrass".to_string())]
+        );
+        assert_eq!(
+            norm.search_page("%", 1, 1).await.unwrap(),
+            vec![(2, "jazz".to_string())]
+        );
+    });
+}
+
+/// Clones share a worker and may be moved between threads.
+#[test]
+fn it_moves_between_threads() {
+    let norm = new_normal();
+    let other = norm.clone();
+    std::thread::spawn(move || block_on(other.create("choro")).unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(block_on(norm.get(1)).unwrap(), "choro");
+}
+
+/// Pairs are inserted and streamed in both directions.
+#[test]
+fn it_streams_pairs() {
+    let pairs = new_pairs();
+    block_on(async {
+        pairs.insert(1, 2).await.unwrap();
+        pairs.insert(1, 3).await.unwrap();
+        pairs.insert(4, 3).await.unwrap();
+        let values: Vec<_> = pairs.get(1).map(Result::unwrap).collect().await;
+        assert_eq!(values, vec![2, 3]);
+        let keys: Vec<_> = pairs.invert(3).map(Result::unwrap).collect().await;
+        assert_eq!(keys, vec![1, 4]);
+        assert_eq!(pairs.get_page(1, 2, 10).await.unwrap(), vec![3]);
+        assert_eq!(pairs.invert_page(3, 0, 1).await.unwrap(), vec![1]);
+        assert_eq!(
+            pairs.page_left(0, 10, 2).await.unwrap(),
+            vec![(1, 2), (1, 3)]
+        );
+        assert_eq!(
+            pairs.page_right(3, 4, 10).await.unwrap(),
+            vec![(1, 3), (4, 3)]
+        );
+        let page = pairs.get_paged(1, None, 1).await.unwrap();
+        assert_eq!(page.items, vec![2]);
+        let page = pairs.get_paged(1, page.next, 1).await.unwrap();
+        assert_eq!((page.items, page.next), (vec![3], None));
+        let page = pairs.invert_paged(3, None, 5).await.unwrap();
+        assert_eq!(page.items, vec![1, 4]);
+        let page = pairs.pairs_paged(PairColumn::Right, None, 2).await.unwrap();
+        assert_eq!(page.items, vec![(1, 2), (1, 3)]);
+        let page = pairs
+            .pairs_paged(PairColumn::Right, page.next, 2)
+            .await
+            .unwrap();
+        assert_eq!((page.items, page.next), (vec![(4, 3)], None));
+    });
+}
+
+/// Pairs are removed and replaced with affected-row counts.
+#[test]
+fn it_removes_pairs() {
+    let pairs = new_pairs();
+    block_on(async {
+        pairs.insert(1, 2).await.unwrap();
+        pairs.insert(1, 3).await.unwrap();
+        pairs.insert(4, 3).await.unwrap();
+        let counts = pairs.insert_bulk(vec![(1, 2), (5, 6)]).await.unwrap();
+        assert_eq!((counts.inserted, counts.existing), (1, 1));
+        assert_eq!(pairs.remove_left(5).await.unwrap(), 1);
+        assert_eq!(pairs.replace_left(1, vec![3, 5]).await.unwrap(), 2);
+        assert_eq!(pairs.remove(1, 5).await.unwrap(), 1);
+        assert_eq!(pairs.remove_right(3).await.unwrap(), 2);
+        assert_eq!(pairs.remove_left(4).await.unwrap(), 0);
+    });
+}
+
+/// Full-text and fuzzy matches are returned best first.
+#[test]
+fn it_streams_full_text_results() {
+    let normal = Normal::new(":memory:", "genres", "genre").unwrap();
+    normal.enable_fts(std::iter::empty::<&str>()).unwrap();
+    normal.enable_fuzzy().unwrap();
+    let norm = AsyncNormal::new(normal).unwrap();
+    block_on(async {
+        norm.create("acid jazz").await.unwrap();
+        norm.create("jazz").await.unwrap();
+        norm.create("blues").await.unwrap();
+        let ids: Vec<_> = norm
+            .search_fts("jazz")
+            .map(|result| result.unwrap().0)
+            .collect()
+            .await;
+        assert_eq!(ids, vec![2, 1]);
+        let matches = norm.lookup_fuzzy("jaz", 0.5, 5).await.unwrap();
+        assert_eq!(matches[0].1, "jazz");
+        let keys: Vec<_> = norm
+            .search_with_mode("^j", SearchMode::Regex)
+            .map(|result| result.unwrap().1)
+            .collect()
+            .await;
+        assert_eq!(keys, vec!["jazz"]);
+        assert_eq!(
+            norm.search_page_with_mode("acid", SearchMode::Prefix, 0, 5)
+                .await
+                .unwrap(),
+            vec![(1, "acid jazz".to_string())]
+        );
+        let page = norm
+            .search_paged("jazz", SearchMode::Contains, None, 1)
+            .await
+            .unwrap();
+        assert_eq!(page.items, vec![(1, "acid jazz".to_string())]);
+        let page = norm
+            .search_paged("jazz", SearchMode::Contains, page.next, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            (page.items, page.next),
+            (vec![(2, "jazz".to_string())], None)
+        );
+    });
+}
+
+/// Read failures are yielded from the stream.
+#[test]
+fn it_streams_errors() {
+    let norm = new_normal();
+    block_on(norm.create("bluegrass")).unwrap();
+    let pattern = "%".repeat(60000); // exceeds SQLite's LIKE pattern limit.
+    let found: Vec<_> = block_on(norm.search(&pattern).collect());
+    assert_eq!(found.len(), 1);
+    assert!(matches!(found[0], Err(NormalError::Sqlite { .. })));
+}
+
+/// Aliases are managed and searched asynchronously.
+#[test]
+fn it_manages_aliases() {
+    let norm = new_normal();
+    block_on(async {
+        let rnb = norm.create("r&b").await.unwrap();
+        norm.add_alias(rnb, "rnb").await.unwrap();
+        assert_eq!(norm.aliases(rnb).await.unwrap(), vec!["rnb"]);
+        assert_eq!(norm.search_with_aliases("rn%").count().await, 1);
+        assert!(norm.remove_alias("rnb").await.unwrap());
+    });
+}
+
+/// Streams read no further ahead of the consumer than their buffer.
+#[test]
+fn streams_wait_for_consumers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let worker = Worker::spawn(1).unwrap();
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = sent.clone();
+    let mut stream = Box::pin(worker.stream(move |items| {
+        for i in 0..1000 {
+            if !send_blocking(items, i) {
+                return;
+            }
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }));
+    block_on(async {
+        assert_eq!(stream.next().await, Some(0));
+    });
+    thread::sleep(std::time::Duration::from_millis(50));
+    assert!(sent.load(Ordering::SeqCst) <= STREAM_BUFFER + 2);
+    drop(stream);
+}
+
+/// A panicking call is reported as an error, and the worker keeps serving.
+#[test]
+fn error_on_worker_panic() {
+    let worker = Worker::spawn(1).unwrap();
+    block_on(async {
+        let result: Result<(), NormalError> = worker.call(|| panic!("boom")).await;
+        assert!(matches!(result, Err(NormalError::WorkerFailed(_))));
+        assert_eq!(worker.call(|| Ok(7)).await.unwrap(), 7);
+    });
+}
+
+/// Several threads serve calls while a stream is unread.
+#[test]
+fn it_serves_calls_concurrently() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let db_path = tmpdir.path().join("genres.sqlite3");
+    let db = Database::open_pool(db_path.to_str().unwrap(), 2).unwrap();
+    let norm = AsyncNormal::new_with_threads(db.normal("genres", "genre").unwrap(), 2).unwrap();
+    block_on(async {
+        let values = (0..200).map(|i| format!("genre {}", i)).collect();
+        norm.create_bulk(values).await.unwrap();
+        let mut genres = Box::pin(norm.search("%"));
+        assert!(genres.next().await.is_some());
+        assert_eq!(norm.get(1).await.unwrap(), "genre 0");
+    });
+}
@@ -0,0 +1,94 @@
+use crate::regexp::register_regexp;
+use crate::NormalError;
+use sqlite::{Connection, Statement};
+use sqlite3_sys as ffi;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::raw::c_int;
+use std::ptr;
+
+/// A connection that prepares each distinct query once and reuses it.
+pub(crate) struct CachedConnection {
+    // Statements borrow the connection, so they are declared, and dropped, first.
+    statements: RefCell<HashMap<String, Statement<'static>>>,
+    conn: Connection,
+}
+
+// SAFETY: the cached statements are only reachable through the connection
+// that owns them, so they always move between threads together.
+unsafe impl Send for CachedConnection {}
+
+impl CachedConnection {
+    /// Wrap a connection, registering the functions queries rely on.
+    pub(crate) fn new(conn: Connection) -> Result<CachedConnection, NormalError> {
+        disable_quoted_strings(&conn)?;
+        register_regexp(&conn)?;
+        Ok(CachedConnection {
+            statements: RefCell::new(HashMap::new()),
+            conn,
+        })
+    }
+
+    /// Return the underlying connection, e.g. to open a cursor.
+    pub(crate) fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Run `f` against the prepared statement for `query`, preparing it on
+    /// first use.  The statement is reset afterwards, releasing any locks.
+    pub(crate) fn with_statement<T>(
+        &self,
+        query: &str,
+        f: impl FnOnce(&mut Statement) -> Result<T, NormalError>,
+    ) -> Result<T, NormalError> {
+        // Take the statement out of the cache while in use, so that `f` may
+        // itself run cached queries.
+        let cached = self.statements.borrow_mut().remove(query);
+        let mut statement = match cached {
+            Some(statement) => statement,
+            None => {
+                let statement = self.conn.prepare(query)?;
+                // SAFETY: the statement is stored alongside the connection it
+                // borrows and is dropped before it; see field order above.
+                unsafe { std::mem::transmute::<Statement<'_>, Statement<'static>>(statement) }
+            }
+        };
+        let result = f(&mut statement);
+        // Reset reports the error of the last step, which `f` already saw.
+        let _ = statement.reset();
+        self.statements
+            .borrow_mut()
+            .insert(query.to_string(), statement);
+        result
+    }
+}
+
+/// Options to accept double-quoted string literals in statements and in
+/// schema definitions; missing from `sqlite3_sys`.
+const SQLITE_DBCONFIG_DQS_DML: c_int = 1013;
+const SQLITE_DBCONFIG_DQS_DDL: c_int = 1014;
+
+/// Make SQLite reject double-quoted names of missing columns, rather than
+/// reading them as strings, since every identifier is double-quoted.
+fn disable_quoted_strings(conn: &Connection) -> Result<(), NormalError> {
+    for option in [SQLITE_DBCONFIG_DQS_DML, SQLITE_DBCONFIG_DQS_DDL].iter() {
+        // SAFETY: both options take an int and an optional int pointer.
+        let code = unsafe {
+            ffi::sqlite3_db_config(conn.as_raw(), *option, 0 as c_int, ptr::null_mut::<c_int>())
+        };
+        if code != ffi::SQLITE_OK {
+            return Err(NormalError::sqlite(
+                "cannot disable double-quoted strings",
+                sqlite::Error {
+                    code: Some(code as isize),
+                    message: None,
+                },
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "./cached_connection_test.rs"]
+mod cached_connection_test;
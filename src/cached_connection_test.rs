@@ -0,0 +1,73 @@
+use super::*;
+use sqlite::State;
+
+fn new_connection() -> CachedConnection {
+    let conn = sqlite::open(":memory:").unwrap();
+    conn.execute("CREATE TABLE xs (x INTEGER)").unwrap();
+    CachedConnection::new(conn).unwrap()
+}
+
+fn insert(conn: &CachedConnection, x: i64) -> Result<(), NormalError> {
+    conn.with_statement("INSERT INTO xs (x) VALUES (?)", |statement| {
+        statement.bind(1, x)?;
+        statement.next()?;
+        Ok(())
+    })
+}
+
+/// Ensure that a query is prepared once and reused.
+#[test]
+fn it_reuses_statements() {
+    let conn = new_connection();
+    insert(&conn, 1).unwrap();
+    insert(&conn, 2).unwrap();
+    assert_eq!(conn.statements.borrow().len(), 1);
+
+    let count = conn
+        .with_statement("SELECT COUNT(*) FROM xs", |statement| {
+            statement.next()?;
+            Ok(statement.read::<i64>(0)?)
+        })
+        .unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(conn.statements.borrow().len(), 2);
+}
+
+/// Cached statements may run other cached statements.
+#[test]
+fn it_nests_statements() {
+    let conn = new_connection();
+    conn.conn().execute("CREATE TABLE ys (y INTEGER)").unwrap();
+    conn.conn()
+        .execute("INSERT INTO ys (y) VALUES (1)")
+        .unwrap();
+    conn.with_statement("SELECT y FROM ys", |statement| {
+        while statement.next()? == State::Row {
+            insert(&conn, statement.read::<i64>(0)?)?;
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(conn.statements.borrow().len(), 2);
+}
+
+/// Statements that fail to prepare are not cached.
+#[test]
+fn it_reports_prepare_errors() {
+    let conn = new_connection();
+    assert!(conn.with_statement("SELECT y FROM xs", |_| Ok(())).is_err());
+    assert!(conn.statements.borrow().is_empty());
+}
+
+/// Double-quoted names of missing columns are errors, not strings.
+#[test]
+fn error_on_double_quoted_strings() {
+    let conn = new_connection();
+    let err = conn
+        .with_statement("SELECT \"y\" FROM xs", |statement| {
+            statement.next()?;
+            Ok(())
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("no such column: y"));
+}
@@ -0,0 +1,89 @@
+use crate::cached_connection::CachedConnection;
+use crate::NormalError;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+use sqlite::{Connection, OpenFlags};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Connections shared between tables and threads.
+pub(crate) type SharedConnection = Arc<ConnectionPool>;
+
+/// How long a connection waits for another to release the file.
+const BUSY_TIMEOUT_MS: usize = 5000;
+
+/// A connection for writes, plus optional read-only connections to the
+/// same file for concurrent reads.
+///
+/// Locks are reentrant so a thread may write while reading search results.
+pub(crate) struct ConnectionPool {
+    writer: ReentrantMutex<CachedConnection>,
+    readers: Vec<ReentrantMutex<CachedConnection>>,
+    next_reader: AtomicUsize,
+}
+
+impl ConnectionPool {
+    /// Share one connection for both reads and writes.
+    pub(crate) fn single(conn: Connection) -> Result<SharedConnection, NormalError> {
+        Ok(Arc::new(ConnectionPool {
+            writer: ReentrantMutex::new(CachedConnection::new(conn)?),
+            readers: vec![],
+            next_reader: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Open a file in WAL mode with `readers` read-only connections.
+    /// In-memory databases cannot be shared, so they get no readers.
+    pub(crate) fn open(file_name: &str, readers: usize) -> Result<SharedConnection, NormalError> {
+        let mut writer = open(file_name, OpenFlags::new().set_create().set_read_write())?;
+        writer.execute("PRAGMA journal_mode = WAL;").map_err(|e| {
+            NormalError::sqlite(format!("cannot enable WAL mode for {}", file_name), e)
+        })?;
+        writer.set_busy_timeout(BUSY_TIMEOUT_MS)?;
+        let readers = if file_name == ":memory:" { 0 } else { readers };
+        let readers = (0..readers)
+            .map(|_| {
+                let mut reader = open(file_name, OpenFlags::new().set_read_only())?;
+                reader.set_busy_timeout(BUSY_TIMEOUT_MS)?;
+                Ok(ReentrantMutex::new(CachedConnection::new(reader)?))
+            })
+            .collect::<Result<Vec<_>, NormalError>>()?;
+        Ok(Arc::new(ConnectionPool {
+            writer: ReentrantMutex::new(CachedConnection::new(writer)?),
+            readers,
+            next_reader: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Lock the connection for writes and transactions.
+    pub(crate) fn writer(&self) -> ReentrantMutexGuard<'_, CachedConnection> {
+        self.writer.lock()
+    }
+
+    /// Lock a connection for reads, preferring an idle reader.  A thread
+    /// holding the writer, e.g. in a transaction, reads through it so that
+    /// it sees its own changes.
+    pub(crate) fn reader(&self) -> ReentrantMutexGuard<'_, CachedConnection> {
+        if self.readers.is_empty() || self.writer.is_owned_by_current_thread() {
+            return self.writer.lock();
+        }
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        let n = self.readers.len();
+        // A reader this thread already holds is always available, so
+        // blocking below never waits on ourselves.
+        for i in 0..n {
+            if let Some(guard) = self.readers[(start + i) % n].try_lock() {
+                return guard;
+            }
+        }
+        self.readers[start % n].lock()
+    }
+}
+
+fn open(file_name: &str, flags: OpenFlags) -> Result<Connection, NormalError> {
+    Connection::open_with_flags(file_name, flags)
+        .map_err(|e| NormalError::sqlite(format!("cannot open {}", file_name), e))
+}
+
+#[cfg(test)]
+#[path = "./connection_pool_test.rs"]
+mod connection_pool_test;
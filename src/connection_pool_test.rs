@@ -0,0 +1,75 @@
+use super::*;
+use tempfile::tempdir;
+
+fn same(a: &CachedConnection, b: &CachedConnection) -> bool {
+    std::ptr::eq(a, b)
+}
+
+/// Pools put the file in WAL mode so readers do not block the writer.
+#[test]
+fn it_enables_wal() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("pool.sqlite3");
+    let pool = ConnectionPool::open(db_path.to_str().unwrap(), 2).unwrap();
+    let mode = pool
+        .writer()
+        .with_statement("PRAGMA journal_mode;", |statement| {
+            statement.next()?;
+            Ok(statement.read::<String>(0)?)
+        })
+        .unwrap();
+    assert_eq!(mode, "wal");
+}
+
+/// Threads read through separate connections at the same time.
+#[test]
+fn it_reads_concurrently() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("pool.sqlite3");
+    let pool = ConnectionPool::open(db_path.to_str().unwrap(), 2).unwrap();
+
+    let reader = pool.reader();
+    assert!(!same(&reader, &pool.writer()));
+    let other = pool.clone();
+    let distinct = std::thread::spawn(move || {
+        let theirs = other.reader();
+        (&*theirs as *const CachedConnection) as usize
+    })
+    .join()
+    .unwrap();
+    assert_ne!(distinct, (&*reader as *const CachedConnection) as usize);
+}
+
+/// Readers are read-only.
+#[test]
+fn it_rejects_writes_to_readers() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("pool.sqlite3");
+    let pool = ConnectionPool::open(db_path.to_str().unwrap(), 1).unwrap();
+    pool.writer()
+        .conn()
+        .execute("CREATE TABLE xs (x INTEGER)")
+        .unwrap();
+    assert!(pool
+        .reader()
+        .conn()
+        .execute("INSERT INTO xs VALUES (1)")
+        .is_err());
+}
+
+/// A thread holding the writer reads its own uncommitted changes.
+#[test]
+fn it_reads_through_the_writer() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("pool.sqlite3");
+    let pool = ConnectionPool::open(db_path.to_str().unwrap(), 2).unwrap();
+    let writer = pool.writer();
+    assert!(same(&pool.reader(), &writer));
+}
+
+/// In-memory databases cannot be shared, so all calls use the writer.
+#[test]
+fn it_has_no_readers_in_memory() {
+    let pool = ConnectionPool::open(":memory:", 2).unwrap();
+    assert!(same(&pool.reader(), &pool.writer()));
+}
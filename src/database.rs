@@ -0,0 +1,120 @@
+use crate::connection_pool::{ConnectionPool, SharedConnection};
+use crate::{
+    ColumnType, IdPairs, NonKeyColumn, Normal, NormalError, NormalizationPolicy, Transaction,
+};
+
+/// A database file holding several normalization and pair tables.
+///
+/// Tables opened from the same `Database` share its connection, so a
+/// transaction begun here spans all of them.  Handles and tables may be
+/// cloned and shared between threads.  Calls are serialized on a single
+/// connection unless the database is opened with `open_pool`.
+#[derive(Clone)]
+pub struct Database {
+    conn: SharedConnection,
+}
+
+impl Database {
+    /// Open or create a database file.
+    pub fn open(file_name: &str) -> Result<Database, NormalError> {
+        let conn = sqlite::open(file_name)
+            .map_err(|e| NormalError::sqlite(format!("cannot open {}", file_name), e))?;
+        Ok(Database {
+            conn: ConnectionPool::single(conn)?,
+        })
+    }
+
+    /// Open or create a database file in WAL mode with `readers` read-only
+    /// connections, so that reads such as `get`, `get_bulk` and `search`
+    /// run concurrently while writes are serialized.
+    pub fn open_pool(file_name: &str, readers: usize) -> Result<Database, NormalError> {
+        Ok(Database {
+            conn: ConnectionPool::open(file_name, readers)?,
+        })
+    }
+
+    /// Open a normalization table, creating it if necessary.
+    pub fn normal(&self, table_name: &str, column_name: &str) -> Result<Normal, NormalError> {
+        Normal::open(
+            self.conn.clone(),
+            table_name,
+            column_name,
+            std::iter::empty(),
+            NormalizationPolicy::default(),
+        )
+    }
+
+    /// Open a normalization table with extra non-key columns, adding them
+    /// if necessary.
+    pub fn normal_with_nonkeys<T: AsRef<str>>(
+        &self,
+        table_name: &str,
+        column_name: &str,
+        nonkeys: impl Iterator<Item = T>,
+    ) -> Result<Normal, NormalError> {
+        Normal::open(
+            self.conn.clone(),
+            table_name,
+            column_name,
+            nonkeys.map(|nonkey| NonKeyColumn::new(nonkey.as_ref(), ColumnType::Text)),
+            NormalizationPolicy::default(),
+        )
+    }
+
+    /// Open a normalization table with typed non-key columns, adding them
+    /// if necessary.
+    pub fn normal_with_typed_nonkeys(
+        &self,
+        table_name: &str,
+        column_name: &str,
+        nonkeys: impl IntoIterator<Item = NonKeyColumn>,
+    ) -> Result<Normal, NormalError> {
+        Normal::open(
+            self.conn.clone(),
+            table_name,
+            column_name,
+            nonkeys.into_iter(),
+            NormalizationPolicy::default(),
+        )
+    }
+
+    /// Open a normalization table normalizing values with a policy.
+    pub fn normal_with_policy(
+        &self,
+        table_name: &str,
+        column_name: &str,
+        policy: NormalizationPolicy,
+    ) -> Result<Normal, NormalError> {
+        Normal::open(
+            self.conn.clone(),
+            table_name,
+            column_name,
+            std::iter::empty(),
+            policy,
+        )
+    }
+
+    /// Open a pairs table, creating it if necessary.
+    pub fn id_pairs(
+        &self,
+        table_name: &str,
+        left_column_name: &str,
+        right_column_name: &str,
+    ) -> Result<IdPairs, NormalError> {
+        IdPairs::open(
+            self.conn.clone(),
+            table_name,
+            left_column_name,
+            right_column_name,
+        )
+    }
+
+    /// Begin a transaction across all tables, rolled back unless committed.
+    pub fn transaction(&self) -> Result<Transaction<'_>, NormalError> {
+        Transaction::begin(self.conn.writer())
+    }
+}
+
+#[cfg(test)]
+#[path = "./database_test.rs"]
+mod database_test;
@@ -0,0 +1,150 @@
+use super::*;
+use tempfile::tempdir;
+
+/// Tables opened from one database see each other's writes.
+#[test]
+fn it_shares_a_connection() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let artists = db.normal("artists", "artist").unwrap();
+    let pairs = db.id_pairs("genre_artists", "genre", "artist").unwrap();
+
+    let genre = genres.create("bluegrass").unwrap();
+    let artist = artists.create("Bill Monroe").unwrap();
+    pairs.insert(genre, artist).unwrap();
+
+    let reopened = db.normal("genres", "genre").unwrap();
+    assert_eq!(reopened.get(genre).unwrap(), "bluegrass");
+    assert_eq!(pairs.get(genre).unwrap().next().unwrap().unwrap(), artist);
+}
+
+/// Rolling back a database transaction undoes writes to every table.
+#[test]
+fn it_rolls_back_across_tables() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let artists = db.normal("artists", "artist").unwrap();
+    let pairs = db.id_pairs("genre_artists", "genre", "artist").unwrap();
+
+    let tx = db.transaction().unwrap();
+    let genre = genres.create("bluegrass").unwrap();
+    let artist = artists.create("Bill Monroe").unwrap();
+    pairs.insert(genre, artist).unwrap();
+    tx.rollback().unwrap();
+
+    assert_eq!(genres.search("%").unwrap().count(), 0);
+    assert_eq!(artists.search("%").unwrap().count(), 0);
+    assert_eq!(pairs.get(genre).unwrap().count(), 0);
+}
+
+/// Committed cross-table writes persist in the file.
+#[test]
+fn it_commits_across_tables() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("normal.sqlite3");
+    let db_name = db_path.to_str().unwrap();
+    {
+        let db = Database::open(db_name).unwrap();
+        let genres = db.normal("genres", "genre").unwrap();
+        let pairs = db.id_pairs("genre_artists", "genre", "artist").unwrap();
+        let tx = db.transaction().unwrap();
+        let genre = genres.create("bluegrass").unwrap();
+        pairs.insert(genre, 7).unwrap();
+        tx.commit().unwrap();
+    }
+
+    let pairs = IdPairs::new(db_name, "genre_artists", "genre", "artist").unwrap();
+    assert_eq!(pairs.get(1).unwrap().next().unwrap().unwrap(), 7);
+}
+
+/// Tables from a database are held across threads while a transaction on
+/// one of them excludes the others.
+#[test]
+fn it_serializes_transactions_across_threads() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let other_genres = db.normal("genres", "genre").unwrap();
+
+    let tx = db.transaction().unwrap();
+    let writer = std::thread::spawn(move || other_genres.create("jazz").unwrap());
+    genres.create("bluegrass").unwrap();
+    tx.rollback().unwrap();
+    writer.join().unwrap();
+
+    let found: Vec<(i64, String)> = genres
+        .search("%")
+        .unwrap()
+        .collect::<Result<_, NormalError>>()
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].1, "jazz");
+}
+
+/// Pooled tables serve reads from several threads while writes continue.
+#[test]
+fn it_reads_from_a_pool() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("normal.sqlite3");
+    let db = Database::open_pool(db_path.to_str().unwrap(), 4).unwrap();
+    let genres = std::sync::Arc::new(db.normal("genres", "genre").unwrap());
+    let ids = genres
+        .create_bulk(["blues", "jazz", "punk"].iter())
+        .unwrap();
+
+    let readers: Vec<_> = (0..8)
+        .map(|_| {
+            let genres = genres.clone();
+            let ids = ids.clone();
+            std::thread::spawn(move || {
+                assert!(genres.get_bulk(&ids).unwrap().iter().all(Option::is_some));
+                assert_eq!(genres.get(ids[1]).unwrap(), "jazz");
+                genres.search("%").unwrap().count()
+            })
+        })
+        .collect();
+    genres.create("choro").unwrap();
+    for reader in readers {
+        assert!(reader.join().unwrap() >= 3);
+    }
+    assert_eq!(genres.search("%").unwrap().count(), 4);
+}
+
+/// Pooled reads inside a transaction see its uncommitted writes.
+#[test]
+fn it_reads_own_writes_from_a_pool() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("normal.sqlite3");
+    let db = Database::open_pool(db_path.to_str().unwrap(), 2).unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+
+    let tx = db.transaction().unwrap();
+    let id = genres.create("bluegrass").unwrap();
+    assert_eq!(genres.get(id).unwrap(), "bluegrass");
+    tx.rollback().unwrap();
+    assert!(matches!(genres.get(id), Err(NormalError::NotFound(_))));
+}
+
+/// A failed commit rolls back, leaving no transaction open.
+#[test]
+fn it_rolls_back_failed_commits() {
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("genres.sqlite3");
+    let db_path = db_path.to_str().unwrap();
+    let db = Database::open(db_path).unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    genres.create_bulk(["jazz", "blues"].iter()).unwrap();
+
+    let other = Database::open(db_path).unwrap();
+    let other_genres = other.normal("genres", "genre").unwrap();
+    let mut reading = other_genres.search("%").unwrap();
+    assert!(reading.next().is_some()); // holds a read lock on the file.
+
+    let tx = db.transaction().unwrap();
+    genres.create("choro").unwrap();
+    assert!(tx.commit().is_err());
+    drop(reading);
+
+    genres.create("later").unwrap();
+    assert_eq!(genres.lookup("choro").unwrap(), None);
+    assert!(other_genres.lookup("later").unwrap().is_some());
+}
@@ -0,0 +1,125 @@
+use crate::ColumnType;
+use std::error::Error;
+use std::fmt;
+
+/// SQLite primary result codes that we distinguish.
+const SQLITE_IOERR: isize = 10;
+const SQLITE_CANTOPEN: isize = 14;
+const SQLITE_CONSTRAINT: isize = 19;
+
+/// Errors reported by normalization tables.
+#[derive(Debug)]
+pub enum NormalError {
+    /// No row exists for the requested id, key, or value.
+    NotFound(String),
+    /// A uniqueness or other table constraint was violated.
+    Constraint {
+        context: String,
+        source: sqlite::Error,
+    },
+    /// The value already belongs to another id, e.g. as its alias.
+    Conflict { value: String, id: i64 },
+    /// The id is still referenced by a pairs table.
+    Referenced { id: i64, table: String },
+    /// The table layout does not match the request, e.g. a missing column.
+    SchemaMismatch(String),
+    /// A non-key column holds a value of another type than requested.
+    TypeMismatch {
+        column: String,
+        expected: ColumnType,
+        found: ColumnType,
+    },
+    /// A page cursor is malformed or belongs to another listing.
+    InvalidCursor(String),
+    /// An async worker thread could not be started, or panicked before
+    /// replying to a call.
+    WorkerFailed(String),
+    /// A table, column, or index name failed validation.
+    InvalidIdentifier { name: String, reason: &'static str },
+    /// The database file could not be opened, read, or written.
+    Io {
+        context: String,
+        source: sqlite::Error,
+    },
+    /// Any other SQLite failure, e.g. a busy or corrupt database.
+    Sqlite {
+        context: String,
+        source: sqlite::Error,
+    },
+}
+
+impl NormalError {
+    /// Wrap an SQLite error, classifying it by result code.
+    pub(crate) fn sqlite(context: impl Into<String>, source: sqlite::Error) -> NormalError {
+        let context = context.into();
+        match source.code {
+            Some(SQLITE_CONSTRAINT) => NormalError::Constraint { context, source },
+            Some(SQLITE_IOERR) | Some(SQLITE_CANTOPEN) => NormalError::Io { context, source },
+            _ => NormalError::Sqlite { context, source },
+        }
+    }
+
+    /// Return the SQLite result code underlying the error, if any.
+    pub fn code(&self) -> Option<isize> {
+        match self {
+            NormalError::Constraint { source, .. }
+            | NormalError::Io { source, .. }
+            | NormalError::Sqlite { source, .. } => source.code,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NormalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NormalError::NotFound(msg)
+            | NormalError::SchemaMismatch(msg)
+            | NormalError::InvalidCursor(msg)
+            | NormalError::WorkerFailed(msg) => write!(f, "{}", msg),
+            NormalError::Conflict { value, id } => {
+                write!(f, "value {:?} already belongs to id {}", value, id)
+            }
+            NormalError::Referenced { id, table } => {
+                write!(f, "id {} is referenced by table {}", id, table)
+            }
+            NormalError::TypeMismatch {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "non-key column {} holds {}, not {}",
+                column, found, expected
+            ),
+            NormalError::InvalidIdentifier { name, reason } => {
+                write!(f, "invalid identifier {:?}: {}", name, reason)
+            }
+            NormalError::Constraint { context, source }
+            | NormalError::Io { context, source }
+            | NormalError::Sqlite { context, source } => write!(
+                f,
+                "{}: {}",
+                context,
+                source.message.as_deref().unwrap_or("???")
+            ),
+        }
+    }
+}
+
+impl Error for NormalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NormalError::Constraint { source, .. }
+            | NormalError::Io { source, .. }
+            | NormalError::Sqlite { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlite::Error> for NormalError {
+    fn from(e: sqlite::Error) -> Self {
+        NormalError::sqlite("sqlite", e)
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::HashSet;
+
+/// Trigrams of each word of a value, ignoring case.  Words are padded so
+/// that their first and last letters count, e.g. "jazz" gives
+/// "  j", " ja", "jaz", "azz" and "zz ".
+fn padded_trigrams(value: &str) -> HashSet<Vec<char>> {
+    let mut trigrams = HashSet::new();
+    for word in value.to_lowercase().split_whitespace() {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain(Some(' ')).collect();
+        for trigram in padded.windows(3) {
+            trigrams.insert(trigram.to_vec());
+        }
+    }
+    trigrams
+}
+
+/// Similarity of two values by their shared trigrams, from 0 to 1.
+pub(crate) fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let a = padded_trigrams(a);
+    let b = padded_trigrams(b);
+    let shared = a.intersection(&b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+/// An FTS5 query matching values sharing any trigram with the value, or
+/// `None` if it is too short to have one.
+pub(crate) fn trigram_query(value: &str) -> Option<String> {
+    let chars: Vec<char> = value.chars().collect();
+    let mut phrases: Vec<String> = chars
+        .windows(3)
+        .map(|trigram| {
+            let trigram: String = trigram.iter().collect();
+            format!("\"{}\"", trigram.replace('"', "\"\""))
+        })
+        .collect();
+    phrases.sort();
+    phrases.dedup();
+    if phrases.is_empty() {
+        None
+    } else {
+        Some(phrases.join(" OR "))
+    }
+}
+
+#[cfg(test)]
+#[path = "./fuzzy_test.rs"]
+mod fuzzy_test;
@@ -0,0 +1,23 @@
+use super::*;
+
+/// Similar spellings score higher than unrelated ones.
+#[test]
+fn it_scores_similarity() {
+    assert_eq!(trigram_similarity("jazz", "JAZZ"), 1.0);
+    assert_eq!(trigram_similarity("jazz", "punk"), 0.0);
+    assert_eq!(trigram_similarity("", ""), 0.0);
+    let typo = trigram_similarity("blugrass", "bluegrass");
+    assert!(typo > 0.5 && typo < 1.0);
+    assert!(trigram_similarity("blugrass", "blues") < typo);
+}
+
+/// Queries match any trigram, quoting each as a phrase.
+#[test]
+fn it_builds_trigram_queries() {
+    assert_eq!(trigram_query("ab"), None);
+    assert_eq!(
+        trigram_query("jazzz").unwrap(),
+        "\"azz\" OR \"jaz\" OR \"zzz\""
+    );
+    assert_eq!(trigram_query("a\"b").unwrap(), "\"a\"\"b\"");
+}
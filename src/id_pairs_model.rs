@@ -1,105 +1,343 @@
-use crate::{new_search_iterator, NormalError};
-use sqlite::Connection;
+use crate::connection_pool::SharedConnection;
+use crate::page::CursorKind;
+use crate::{
+    check_columns, new_search_iterator, Cursor, Database, Identifier, NormalError, Page,
+    Transaction,
+};
+use sqlite::{Connection, State, Statement, Value};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+/// A column of a pairs table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairColumn {
+    Left,
+    Right,
+}
+
+/// Counts reported by `IdPairs::insert_bulk`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InsertCounts {
+    /// Pairs that were added.
+    pub inserted: usize,
+    /// Pairs that were already present.
+    pub existing: usize,
+}
 
 /// Structure for maintaining pairs in a DB table.
-pub struct IdPairs<'a> {
-    table_name: &'a str,
-    left_column_name: &'a str,
-    right_column_name: &'a str,
-    conn: Connection,
+///
+/// Tables own their names and may be kept in long-lived state or moved
+/// between threads.  Clones share the connection.
+#[derive(Clone)]
+pub struct IdPairs {
+    table_name: Identifier<'static>,
+    left_column_name: Identifier<'static>,
+    right_column_name: Identifier<'static>,
+    conn: SharedConnection,
 }
 
-impl<'a> IdPairs<'a> {
-    pub fn new<'b>(
+impl IdPairs {
+    pub fn new(
         file_name: &str,
-        table_name: &'b str,
-        left_column_name: &'b str,
-        right_column_name: &'b str,
-    ) -> Result<IdPairs<'b>, sqlite::Error> {
+        table_name: &str,
+        left_column_name: &str,
+        right_column_name: &str,
+    ) -> Result<IdPairs, NormalError> {
+        Database::open(file_name)?.id_pairs(table_name, left_column_name, right_column_name)
+    }
+
+    /// Create the table, if necessary, on a shared connection.
+    pub(crate) fn open(
+        conn: SharedConnection,
+        table_name: &str,
+        left_column_name: &str,
+        right_column_name: &str,
+    ) -> Result<IdPairs, NormalError> {
+        let table_name = Identifier::new(table_name.to_string())?;
+        let left_column_name = Identifier::new(left_column_name.to_string())?;
+        let right_column_name = Identifier::new(right_column_name.to_string())?;
+        create_table(
+            conn.writer().conn(),
+            &table_name,
+            &left_column_name,
+            &right_column_name,
+        )?;
         Ok(IdPairs {
-            table_name: table_name,
-            left_column_name: left_column_name,
-            right_column_name: right_column_name,
-            conn: open(file_name, table_name, left_column_name, right_column_name)?,
+            table_name,
+            left_column_name,
+            right_column_name,
+            conn,
         })
     }
 
     /// Look up the values associated with a key.
-    pub fn get(&'a self, key: i64) -> Result<impl 'a + Iterator<Item = i64>, NormalError> {
+    /// Read failures are yielded as errors and end the iteration.
+    pub fn get(
+        &self,
+        key: i64,
+    ) -> Result<impl '_ + Iterator<Item = Result<i64, NormalError>>, NormalError> {
         let query = format!(
-            "SELECT {} FROM {} WHERE {}={}",
-            self.right_column_name, self.table_name, self.left_column_name, key
+            "SELECT {} FROM {} WHERE {} = ?",
+            self.right_column_name.quoted(),
+            self.table_name.quoted(),
+            self.left_column_name.quoted()
         );
-        let cursor = self.conn.prepare(query).unwrap().cursor();
-
-        Ok(new_search_iterator(cursor))
+        new_search_iterator(self.conn.reader(), &query, &[Value::Integer(key)])
     }
 
     /// Look up the values associated with a key and copy them into the
     /// destination.
-    pub fn get_page(
-        &self,
-        key: i64,
-        min_val: i64,
-        dest: &mut Vec<i64>,
-    ) -> Result<usize, NormalError> {
-        let sz = dest.len();
+    pub fn get_page(&self, key: i64, min_val: i64, dest: &mut [i64]) -> Result<usize, NormalError> {
         let query = format!(
-            "SELECT {} FROM {} WHERE {}={} AND {} > {} ORDER BY {} LIMIT {}",
-            self.right_column_name,
-            self.table_name,
-            self.left_column_name,
+            "SELECT {} FROM {} WHERE {} = ? AND {} > ? ORDER BY {} LIMIT ?",
+            self.right_column_name.quoted(),
+            self.table_name.quoted(),
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.right_column_name.quoted()
+        );
+        self.fill_page(
+            "failed to get_page",
+            &query,
             key,
-            self.right_column_name,
             min_val,
-            self.right_column_name,
-            sz
+            dest,
+            |statement| Ok(statement.read::<i64>(0)?),
+        )
+    }
+
+    /// Insert a new key-value pair.
+    pub fn insert(&self, key: i64, val: i64) -> Result<(), NormalError> {
+        self.insert_pair(key, val).map(|_| ())
+    }
+
+    /// Insert a pair, returning whether it was new.
+    fn insert_pair(&self, key: i64, val: i64) -> Result<bool, NormalError> {
+        let query = format!(
+            "INSERT OR IGNORE INTO {} ({}, {}) VALUES (?, ?);",
+            self.table_name.quoted(),
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted()
         );
-        let mut cursor = self.conn.prepare(query).unwrap().cursor();
-        let mut i = 0;
-        while i < sz
-            && match cursor.next() {
-                Ok(Some(row)) => {
-                    dest[i] = row[0].as_integer().unwrap();
-                    true
-                }
-                Ok(None) => false,
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("failed to get_page: {}", unwrap_msg!(e)),
-                    });
+        let conn = self.conn.writer();
+        conn.with_statement(&query, |statement| {
+            statement.bind(1, key)?;
+            statement.bind(2, val)?;
+            match statement.next() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(NormalError::sqlite(
+                    format!("failed to insert {},{}", key, val),
+                    e,
+                )),
+            }
+        })?;
+        Ok(conn.conn().changes() > 0)
+    }
+
+    /// Insert key-value pairs in one transaction with a single prepared
+    /// statement.  On error, none of the pairs are inserted.
+    pub fn insert_bulk(
+        &self,
+        pairs: impl IntoIterator<Item = (i64, i64)>,
+    ) -> Result<InsertCounts, NormalError> {
+        let query = format!(
+            "INSERT OR IGNORE INTO {} ({}, {}) VALUES (?, ?);",
+            self.table_name.quoted(),
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted()
+        );
+        let tx = self.transaction()?;
+        let conn = self.conn.writer();
+        let counts = conn.with_statement(&query, |statement| {
+            let mut counts = InsertCounts::default();
+            for (key, val) in pairs {
+                statement.reset()?;
+                statement.bind(1, key)?;
+                statement.bind(2, val)?;
+                statement.next().map_err(|e| {
+                    NormalError::sqlite(format!("failed to insert {},{}", key, val), e)
+                })?;
+                if conn.conn().changes() > 0 {
+                    counts.inserted += 1;
+                } else {
+                    counts.existing += 1;
                 }
             }
-        {
-            i += 1;
+            Ok(counts)
+        })?;
+        drop(conn);
+        tx.commit()?;
+        Ok(counts)
+    }
+
+    /// Remove a key-value pair, returning the number of pairs removed.
+    pub fn remove(&self, key: i64, val: i64) -> Result<usize, NormalError> {
+        let query = format!(
+            "DELETE FROM {} WHERE {} = ? AND {} = ?;",
+            self.table_name.quoted(),
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted()
+        );
+        let conn = self.conn.writer();
+        conn.with_statement(&query, |statement| {
+            statement.bind(1, key)?;
+            statement.bind(2, val)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("failed to remove {},{}", key, val), e))
+        })?;
+        Ok(conn.conn().changes())
+    }
+
+    /// Remove every pair with the key, returning the number removed.
+    pub fn remove_left(&self, key: i64) -> Result<usize, NormalError> {
+        self.remove_id(PairColumn::Left, key)
+    }
+
+    /// Remove every pair with the value, returning the number removed.
+    pub fn remove_right(&self, val: i64) -> Result<usize, NormalError> {
+        self.remove_id(PairColumn::Right, val)
+    }
+
+    /// Atomically replace the values associated with a key, returning the
+    /// number of pairs removed or inserted.  Unchanged pairs are kept.
+    pub fn replace_left(
+        &self,
+        key: i64,
+        new_vals: impl IntoIterator<Item = i64>,
+    ) -> Result<usize, NormalError> {
+        let new_vals: HashSet<i64> = new_vals.into_iter().collect();
+        let tx = self.transaction()?;
+        let old_vals = self
+            .get(key)?
+            .collect::<Result<HashSet<i64>, NormalError>>()?;
+        let mut affected = 0;
+        for val in old_vals.difference(&new_vals) {
+            affected += self.remove(key, *val)?;
+        }
+        for val in new_vals.difference(&old_vals) {
+            if self.insert_pair(key, *val)? {
+                affected += 1;
+            }
         }
-        Ok(i)
+        tx.commit()?;
+        Ok(affected)
     }
 
-    /// Insert a new key-value pair.
-    pub fn insert(&self, key: i64, val: i64) -> Result<(), NormalError> {
+    /// Replace an id with another in one column, e.g. after merging
+    /// normalized values.  Pairs that would duplicate an existing pair are
+    /// dropped.  Returns the number of pairs that held the old id.
+    pub fn replace_id(&self, column: PairColumn, from: i64, to: i64) -> Result<usize, NormalError> {
+        if from == to {
+            return Ok(0);
+        }
+        let column_name = self.column_name(column);
+        let update = format!(
+            "UPDATE OR IGNORE {} SET {} = ? WHERE {} = ?;",
+            self.table_name.quoted(),
+            column_name.quoted(),
+            column_name.quoted()
+        );
+        // Rows left behind by the update would have duplicated a pair.
+        let delete = format!(
+            "DELETE FROM {} WHERE {} = ?;",
+            self.table_name.quoted(),
+            column_name.quoted()
+        );
+        let tx = self.transaction()?;
+        let conn = self.conn.writer();
+        let context = || format!("cannot replace {} with {} in {}", from, to, self.table_name);
+        conn.with_statement(&update, |statement| {
+            statement.bind(1, to)?;
+            statement.bind(2, from)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(context(), e))
+        })?;
+        let updated = conn.conn().changes();
+        conn.with_statement(&delete, |statement| {
+            statement.bind(1, from)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(context(), e))
+        })?;
+        let deleted = conn.conn().changes();
+        tx.commit()?;
+        Ok(updated + deleted)
+    }
+
+    /// Whether any pair holds the id in a column.
+    pub(crate) fn contains_id(&self, column: PairColumn, id: i64) -> Result<bool, NormalError> {
         let query = format!(
-            "INSERT OR IGNORE INTO {} ({}, {}) VALUES ({}, {});",
-            self.table_name, self.left_column_name, self.right_column_name, key, val
+            "SELECT 1 FROM {} WHERE {} = ? LIMIT 1",
+            self.table_name.quoted(),
+            self.column_name(column).quoted()
         );
-        let mut statement = self.conn.prepare(query).unwrap();
-        match statement.next() {
-            Ok(_) => Ok(()),
-            Err(_) => Err(NormalError {
-                msg: format!("failed to insert {},{}", key, val),
-            }),
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            match statement.next() {
+                Ok(state) => Ok(state == State::Row),
+                Err(e) => Err(NormalError::sqlite(
+                    format!("cannot find {} in {}", id, self.table_name),
+                    e,
+                )),
+            }
+        })
+    }
+
+    /// Remove the pairs holding the id in a column, returning how many.
+    fn remove_id(&self, column: PairColumn, id: i64) -> Result<usize, NormalError> {
+        let query = format!(
+            "DELETE FROM {} WHERE {} = ?;",
+            self.table_name.quoted(),
+            self.column_name(column).quoted()
+        );
+        let conn = self.conn.writer();
+        conn.with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            statement.next().map_err(|e| {
+                NormalError::sqlite(format!("cannot remove {} from {}", id, self.table_name), e)
+            })
+        })?;
+        Ok(conn.conn().changes())
+    }
+
+    /// Return the table name.
+    pub(crate) fn table_name(&self) -> &Identifier<'static> {
+        &self.table_name
+    }
+
+    fn column_name(&self, column: PairColumn) -> &Identifier<'static> {
+        match column {
+            PairColumn::Left => &self.left_column_name,
+            PairColumn::Right => &self.right_column_name,
         }
     }
 
+    /// Whether the table shares a connection with another, e.g. so that a
+    /// transaction spans both.
+    pub(crate) fn shares_connection(&self, conn: &SharedConnection) -> bool {
+        std::sync::Arc::ptr_eq(&self.conn, conn)
+    }
+
+    /// Begin a transaction, rolled back unless committed.
+    pub fn transaction(&self) -> Result<Transaction<'_>, NormalError> {
+        Transaction::begin(self.conn.writer())
+    }
+
     /// Look up the keys associated with a value.
-    pub fn invert(&'a self, val: i64) -> Result<impl 'a + Iterator<Item = i64>, NormalError> {
+    /// Read failures are yielded as errors and end the iteration.
+    pub fn invert(
+        &self,
+        val: i64,
+    ) -> Result<impl '_ + Iterator<Item = Result<i64, NormalError>>, NormalError> {
         let query = format!(
-            "SELECT {} FROM {} WHERE {}={}",
-            self.left_column_name, self.table_name, self.right_column_name, val
+            "SELECT {} FROM {} WHERE {} = ?",
+            self.left_column_name.quoted(),
+            self.table_name.quoted(),
+            self.right_column_name.quoted()
         );
-        let cursor = self.conn.prepare(query).unwrap().cursor();
-
-        Ok(new_search_iterator(cursor))
+        new_search_iterator(self.conn.reader(), &query, &[Value::Integer(val)])
     }
 
     /// Look up the keys associated with a value and copy them into the
@@ -108,156 +346,298 @@ impl<'a> IdPairs<'a> {
         &self,
         value: i64,
         min_key: i64,
-        dest: &mut Vec<i64>,
+        dest: &mut [i64],
     ) -> Result<usize, NormalError> {
-        let sz = dest.len();
         let query = format!(
-            "SELECT {} FROM {} WHERE {}={} AND {} > {} ORDER BY {} LIMIT {}",
-            self.left_column_name,
-            self.table_name,
-            self.right_column_name,
+            "SELECT {} FROM {} WHERE {} = ? AND {} > ? ORDER BY {} LIMIT ?",
+            self.left_column_name.quoted(),
+            self.table_name.quoted(),
+            self.right_column_name.quoted(),
+            self.left_column_name.quoted(),
+            self.left_column_name.quoted()
+        );
+        self.fill_page(
+            "failed to invert_page",
+            &query,
             value,
-            self.left_column_name,
             min_key,
-            self.left_column_name,
-            sz
-        );
-        let mut cursor = self.conn.prepare(query).unwrap().cursor();
-        let mut i = 0;
-        while i < sz
-            && match cursor.next() {
-                Ok(Some(row)) => {
-                    dest[i] = row[0].as_integer().unwrap();
-                    true
-                }
-                Ok(None) => false,
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("failed to invert_page: {}", unwrap_msg!(e)),
-                    });
-                }
-            }
-        {
-            i += 1;
-        }
-        Ok(i)
+            dest,
+            |statement| Ok(statement.read::<i64>(0)?),
+        )
     }
 
+    /// Copy pairs with left ids from `min_key` up to `max_key` into the
+    /// destination, ordered by left and then right id.  Prefer
+    /// `pairs_paged`, which resumes after a cursor without repeating pairs.
     pub fn page_left(
         &self,
         min_key: i64,
         max_key: i64,
-        dest: &mut Vec<(i64, i64)>,
+        dest: &mut [(i64, i64)],
     ) -> Result<usize, NormalError> {
-        let sz = dest.len();
         let query = format!(
-            "SELECT {}, {} FROM {} WHERE {} < {} AND {} >= {} ORDER BY {} LIMIT {}",
-            self.left_column_name,
-            self.right_column_name,
-            self.table_name,
-            self.left_column_name,
+            "SELECT {}, {} FROM {} WHERE {} < ? AND {} >= ? ORDER BY {}, {} LIMIT ?",
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.table_name.quoted(),
+            self.left_column_name.quoted(),
+            self.left_column_name.quoted(),
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted()
+        );
+        self.fill_page(
+            "failed page_left",
+            &query,
             max_key,
-            self.left_column_name,
             min_key,
-            self.left_column_name,
-            sz
-        );
-        let mut cursor = self.conn.prepare(query).unwrap().cursor();
-        let mut i = 0;
-        while i < sz
-            && match cursor.next() {
-                Ok(Some(row)) => {
-                    dest[i].0 = row[0].as_integer().unwrap();
-                    dest[i].1 = row[1].as_integer().unwrap();
-                    true
-                }
-                Ok(None) => false,
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("failed page_left: {}", unwrap_msg!(e)),
-                    });
-                }
-            }
-        {
-            i += 1;
-        }
-        Ok(i)
+            dest,
+            |statement| Ok((statement.read::<i64>(0)?, statement.read::<i64>(1)?)),
+        )
     }
 
+    /// Copy pairs with right ids from `min_key` up to `max_key` into the
+    /// destination, ordered by right and then left id.  Prefer
+    /// `pairs_paged`, which resumes after a cursor without repeating pairs.
     pub fn page_right(
         &self,
         min_key: i64,
         max_key: i64,
-        dest: &mut Vec<(i64, i64)>,
+        dest: &mut [(i64, i64)],
     ) -> Result<usize, NormalError> {
-        let sz = dest.len();
         let query = format!(
-            "SELECT {}, {} FROM {} WHERE {} < {} AND {} >= {} ORDER BY {} LIMIT {}",
-            self.left_column_name,
-            self.right_column_name,
-            self.table_name,
-            self.right_column_name,
+            "SELECT {}, {} FROM {} WHERE {} < ? AND {} >= ? ORDER BY {}, {} LIMIT ?",
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.table_name.quoted(),
+            self.right_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.left_column_name.quoted()
+        );
+        self.fill_page(
+            "failed page_right",
+            &query,
             max_key,
-            self.right_column_name,
             min_key,
-            self.right_column_name,
-            sz
+            dest,
+            |statement| Ok((statement.read::<i64>(0)?, statement.read::<i64>(1)?)),
+        )
+    }
+
+    /// Return a page of up to `limit` values associated with a key, starting
+    /// after the cursor, or from the first value without one.  Pages hold at
+    /// least one item.
+    pub fn get_paged(
+        &self,
+        key: i64,
+        cursor: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<i64>, NormalError> {
+        self.single_paged(CursorKind::Get, PairColumn::Left, key, cursor, limit)
+    }
+
+    /// Return a page of up to `limit` keys associated with a value, starting
+    /// after the cursor, or from the first key without one.  Pages hold at
+    /// least one item.
+    pub fn invert_paged(
+        &self,
+        val: i64,
+        cursor: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<i64>, NormalError> {
+        self.single_paged(CursorKind::Invert, PairColumn::Right, val, cursor, limit)
+    }
+
+    /// Return a page of up to `limit` pairs ordered by the column and then
+    /// the other, starting after the cursor, or from the first pair without
+    /// one.  Pages hold at least one item, and never repeat or skip pairs
+    /// sharing a key.
+    pub fn pairs_paged(
+        &self,
+        order: PairColumn,
+        cursor: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<(i64, i64)>, NormalError> {
+        let (kind, first, second) = match order {
+            PairColumn::Left => (CursorKind::Left, PairColumn::Left, PairColumn::Right),
+            PairColumn::Right => (CursorKind::Right, PairColumn::Right, PairColumn::Left),
+        };
+        let (first, second) = (self.column_name(first), self.column_name(second));
+        let after = match cursor {
+            Some(cursor) => {
+                let (a, b) = cursor.position(kind)?;
+                vec![a, b]
+            }
+            None => vec![],
+        };
+        let query = format!(
+            "SELECT {}, {} FROM {} {} ORDER BY {}, {} LIMIT ?",
+            self.left_column_name.quoted(),
+            self.right_column_name.quoted(),
+            self.table_name.quoted(),
+            if after.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE ({}, {}) > (?, ?)", first.quoted(), second.quoted())
+            },
+            first.quoted(),
+            second.quoted()
         );
-        let mut cursor = self.conn.prepare(query).unwrap().cursor();
-        let mut i = 0;
-        while i < sz
-            && match cursor.next() {
-                Ok(Some(row)) => {
-                    dest[i].0 = row[0].as_integer().unwrap();
-                    dest[i].1 = row[1].as_integer().unwrap();
-                    true
-                }
-                Ok(None) => false,
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("failed page_right: {}", unwrap_msg!(e)),
-                    });
-                }
+        let items = self.read_page(&query, &after, limit, |statement| {
+            Ok((statement.read::<i64>(0)?, statement.read::<i64>(1)?))
+        })?;
+        Ok(Page::from_items(
+            items,
+            limit.max(1),
+            |&(left, right)| match order {
+                PairColumn::Left => Cursor::new(kind, left, right),
+                PairColumn::Right => Cursor::new(kind, right, left),
+            },
+        ))
+    }
+
+    /// Page through the ids paired with an id in a column.
+    fn single_paged(
+        &self,
+        kind: CursorKind,
+        column: PairColumn,
+        id: i64,
+        cursor: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<i64>, NormalError> {
+        let other = match column {
+            PairColumn::Left => self.column_name(PairColumn::Right),
+            PairColumn::Right => self.column_name(PairColumn::Left),
+        };
+        let mut params = vec![id];
+        if let Some(cursor) = cursor {
+            let (cursor_id, after) = cursor.position(kind)?;
+            if cursor_id != id {
+                return Err(NormalError::InvalidCursor(format!(
+                    "cursor {} belongs to id {}",
+                    cursor, cursor_id
+                )));
             }
-        {
-            i += 1;
+            params.push(after);
         }
-        Ok(i)
+        let query = format!(
+            "SELECT {} FROM {} WHERE {} = ? {} ORDER BY {} LIMIT ?",
+            other.quoted(),
+            self.table_name.quoted(),
+            self.column_name(column).quoted(),
+            if cursor.is_some() {
+                format!("AND {} > ?", other.quoted())
+            } else {
+                String::new()
+            },
+            other.quoted()
+        );
+        let items = self.read_page(&query, &params, limit, |statement| {
+            Ok(statement.read::<i64>(0)?)
+        })?;
+        Ok(Page::from_items(items, limit.max(1), |&other_id| {
+            Cursor::new(kind, id, other_id)
+        }))
+    }
+
+    /// Run a cached paging query binding the parameters and then one more
+    /// than the limit, so that the page knows whether another follows.
+    fn read_page<T>(
+        &self,
+        query: &str,
+        params: &[i64],
+        limit: usize,
+        read: fn(&Statement) -> Result<T, NormalError>,
+    ) -> Result<Vec<T>, NormalError> {
+        let fetch = limit.max(1).saturating_add(1);
+        self.conn.reader().with_statement(query, |statement| {
+            for (i, param) in params.iter().enumerate() {
+                statement.bind(i + 1, *param)?;
+            }
+            statement.bind(params.len() + 1, i64::try_from(fetch).unwrap_or(i64::MAX))?;
+            let mut items = vec![];
+            while statement
+                .next()
+                .map_err(|e| NormalError::sqlite("failed to read page", e))?
+                == State::Row
+            {
+                items.push(read(statement)?);
+            }
+            Ok(items)
+        })
+    }
+
+    /// Run a cached paging query binding two bounds and the destination size
+    /// as limit, copying rows into the destination.
+    fn fill_page<T>(
+        &self,
+        context: &str,
+        query: &str,
+        first: i64,
+        second: i64,
+        dest: &mut [T],
+        read: fn(&Statement) -> Result<T, NormalError>,
+    ) -> Result<usize, NormalError> {
+        self.conn.reader().with_statement(query, |statement| {
+            statement.bind(1, first)?;
+            statement.bind(2, second)?;
+            statement.bind(3, i64::try_from(dest.len()).unwrap_or(i64::MAX))?;
+            let mut i = 0;
+            while i < dest.len()
+                && statement
+                    .next()
+                    .map_err(|e| NormalError::sqlite(context, e))?
+                    == State::Row
+            {
+                dest[i] = read(statement)?;
+                i += 1;
+            }
+            Ok(i)
+        })
     }
 }
 
-fn open(
-    path: &str,
-    table_name: &str,
-    left_column_name: &str,
-    right_column_name: &str,
-) -> Result<Connection, sqlite::Error> {
-    let conn = sqlite::open(path).unwrap();
-    let left_index_name = format!("idx_{}_{}", table_name, left_column_name);
-    let right_index_name = format!("idx_{}_{}", table_name, right_column_name);
+/// Create the table and indices, if they do not already exist.
+fn create_table(
+    conn: &Connection,
+    table_name: &Identifier,
+    left_column_name: &Identifier,
+    right_column_name: &Identifier,
+) -> Result<(), NormalError> {
+    let query = format!(
+        "CREATE TABLE IF NOT EXISTS {} ({} INTEGER, {} INTEGER, UNIQUE({}, {}));",
+        table_name.quoted(),
+        left_column_name.quoted(),
+        right_column_name.quoted(),
+        left_column_name.quoted(),
+        right_column_name.quoted()
+    );
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create table {}", table_name), e))?;
+    check_columns(conn, table_name, &[left_column_name, right_column_name])?;
     let query = format!(
         "
-            CREATE TABLE IF NOT EXISTS {} ({} INTEGER, {} INTEGER, UNIQUE({}, {}));
             CREATE INDEX IF NOT EXISTS {} ON {} ({});
             CREATE INDEX IF NOT EXISTS {} ON {} ({});
+            CREATE INDEX IF NOT EXISTS {} ON {} ({}, {});
             ",
-        table_name,
-        left_column_name,
-        right_column_name,
-        left_column_name,
-        right_column_name,
-        left_index_name.as_str(),
-        table_name,
-        left_column_name,
-        right_index_name.as_str(),
-        table_name,
-        right_column_name
+        Identifier::index_name(table_name, left_column_name),
+        table_name.quoted(),
+        left_column_name.quoted(),
+        Identifier::index_name(table_name, right_column_name),
+        table_name.quoted(),
+        right_column_name.quoted(),
+        // Orders pairs by right and then left id for `pairs_paged`.
+        Identifier::index_name(
+            table_name,
+            &Identifier::new(format!("{}_{}", right_column_name, left_column_name))?
+        ),
+        table_name.quoted(),
+        right_column_name.quoted(),
+        left_column_name.quoted()
     );
-    {
-        let mut statement = conn.prepare(query)?;
-        statement.next()?;
-    }
-    Ok(conn)
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create table {}", table_name), e))
 }
 
 #[cfg(test)]
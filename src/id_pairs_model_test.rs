@@ -1,6 +1,6 @@
 use super::*;
 
-fn new_table<'a>() -> Result<IdPairs<'a>, sqlite::Error> {
+fn new_table() -> Result<IdPairs, NormalError> {
     IdPairs::new(":memory:", "xys", "x", "y")
 }
 
@@ -10,6 +10,16 @@ fn it_creates_tables() {
     assert!(new_table().is_ok());
 }
 
+/// Reopening a table with a misspelled column fails.
+#[test]
+fn error_on_misspelled_column() {
+    let db = Database::open(":memory:").unwrap();
+    db.id_pairs("pairs", "left", "right").unwrap();
+    let err = db.id_pairs("pairs", "left", "r").err().unwrap();
+    assert!(matches!(err, NormalError::SchemaMismatch(_)));
+    assert_eq!(err.to_string(), "table pairs has no column r");
+}
+
 /// Ensure that we can insert and retrieve values.
 #[test]
 fn it_inserts_values() {
@@ -18,8 +28,8 @@ fn it_inserts_values() {
     let pairs = new_table().unwrap();
     pairs.insert(x, y).unwrap();
     let mut i = pairs.get(x).unwrap();
-    assert_eq!(i.next().unwrap(), y);
-    assert_eq!(i.next(), None);
+    assert_eq!(i.next().unwrap().unwrap(), y);
+    assert!(i.next().is_none());
 }
 
 /// Ensure that we can reverse retrieve values.
@@ -30,8 +40,8 @@ fn it_inverts_search() {
     let pairs = new_table().unwrap();
     pairs.insert(x, y).unwrap();
     let mut i = pairs.invert(y).unwrap();
-    assert_eq!(i.next().unwrap(), x);
-    assert_eq!(i.next(), None);
+    assert_eq!(i.next().unwrap().unwrap(), x);
+    assert!(i.next().is_none());
 }
 
 /// Demonstrate missing key behavior.
@@ -51,15 +61,29 @@ fn handles_unique_key() {
     pairs.insert(x, y).unwrap();
 
     let mut i = pairs.get(x).unwrap();
-    assert_eq!(i.next().unwrap(), y);
-    assert_eq!(i.next(), None);
+    assert_eq!(i.next().unwrap().unwrap(), y);
+    assert!(i.next().is_none());
+}
+
+/// SQLite keywords are quoted and may be used as names.
+#[test]
+fn it_accepts_keyword_table_names() {
+    let pairs = IdPairs::new(":memory:", "values", "value", "key").unwrap();
+    pairs.insert(1, 2).unwrap();
+    assert_eq!(
+        pairs
+            .get(1)
+            .unwrap()
+            .collect::<Result<Vec<i64>, NormalError>>()
+            .unwrap(),
+        vec![2]
+    );
 }
 
-/// Demonstrate panic from SQLite keyword use -- should we fix this.
+/// Invalid names are reported as errors.
 #[test]
-#[should_panic(expected = "near \\\"values\\\": syntax error")]
-fn demo_invalid_table_setup() {
-    IdPairs::new(":memory:", "values", "value", "key").unwrap();
+fn it_rejects_invalid_table_names() {
+    assert!(IdPairs::new(":memory:", "xys", "sqlite_x", "y").is_err());
 }
 
 #[test]
@@ -71,9 +95,9 @@ fn handles_multiple_values_at_key() {
     pairs.insert(x, y0).unwrap();
     pairs.insert(x, y1).unwrap();
     let mut i = pairs.get(x).unwrap();
-    assert_eq!(i.next().unwrap(), y0);
-    assert_eq!(i.next().unwrap(), y1);
-    assert_eq!(i.next(), None);
+    assert_eq!(i.next().unwrap().unwrap(), y0);
+    assert_eq!(i.next().unwrap().unwrap(), y1);
+    assert!(i.next().is_none());
 }
 
 #[test]
@@ -85,9 +109,9 @@ fn handles_multiple_keys_with_same_value() {
     pairs.insert(y0, x).unwrap();
     pairs.insert(y1, x).unwrap();
     let mut i = pairs.invert(x).unwrap();
-    assert_eq!(i.next().unwrap(), y0);
-    assert_eq!(i.next().unwrap(), y1);
-    assert_eq!(i.next(), None);
+    assert_eq!(i.next().unwrap().unwrap(), y0);
+    assert_eq!(i.next().unwrap().unwrap(), y1);
+    assert!(i.next().is_none());
 }
 
 #[test]
@@ -206,3 +230,203 @@ fn enumerates_by_right_column() {
     assert_eq!(dst[0], (17, 7));
     assert_eq!(dst[1], (13, 19));
 }
+
+/// Get yields an error for values that are not ids.
+#[test]
+fn get_yields_type_errors() {
+    let pairs = new_table().unwrap();
+    pairs
+        .conn
+        .writer()
+        .conn()
+        .execute("INSERT INTO xys (x, y) VALUES (1, 'abc')")
+        .unwrap();
+    let mut i = pairs.get(1).unwrap();
+    assert!(matches!(
+        i.next(),
+        Some(Err(NormalError::SchemaMismatch(_)))
+    ));
+    assert!(i.next().is_none());
+}
+
+/// Callers may group insertions in a transaction.
+#[test]
+fn it_commits_transactions() {
+    let pairs = new_table().unwrap();
+    let tx = pairs.transaction().unwrap();
+    pairs.insert(1, 2).unwrap();
+    pairs.insert(1, 3).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(pairs.get(1).unwrap().count(), 2);
+}
+
+/// Tables own their names, so they may be moved into other threads.
+#[test]
+fn it_moves_between_threads() {
+    let pairs = new_table().unwrap();
+    let pairs = std::thread::spawn(move || {
+        pairs.insert(1, 2).unwrap();
+        pairs
+    })
+    .join()
+    .unwrap();
+    assert_eq!(pairs.get(1).unwrap().next().unwrap().unwrap(), 2);
+}
+
+/// Replacing an id drops pairs that would become duplicates.
+#[test]
+fn it_replaces_ids() {
+    let pairs = new_table().unwrap();
+    pairs.insert(1, 2).unwrap();
+    pairs.insert(1, 3).unwrap();
+    pairs.insert(4, 3).unwrap();
+
+    assert_eq!(pairs.replace_id(PairColumn::Right, 3, 2).unwrap(), 2);
+    let mut dest = [(0, 0); 4];
+    assert_eq!(pairs.page_left(0, 10, &mut dest).unwrap(), 2);
+    assert_eq!(dest[..2], [(1, 2), (4, 2)]);
+
+    assert_eq!(pairs.replace_id(PairColumn::Left, 4, 1).unwrap(), 1);
+    assert_eq!(pairs.get(1).unwrap().count(), 1);
+    assert_eq!(pairs.replace_id(PairColumn::Left, 4, 1).unwrap(), 0);
+}
+
+/// Replacing an id with itself keeps its pairs.
+#[test]
+fn it_replaces_ids_with_themselves() {
+    let pairs = new_table().unwrap();
+    pairs.insert(1, 2).unwrap();
+    pairs.insert(1, 3).unwrap();
+    assert_eq!(pairs.replace_id(PairColumn::Left, 1, 1).unwrap(), 0);
+    assert_eq!(pairs.get(1).unwrap().count(), 2);
+}
+
+/// Removals report how many pairs they deleted.
+#[test]
+fn it_removes_pairs() {
+    let pairs = new_table().unwrap();
+    pairs.insert(1, 2).unwrap();
+    pairs.insert(1, 3).unwrap();
+    pairs.insert(4, 3).unwrap();
+    pairs.insert(5, 6).unwrap();
+
+    assert_eq!(pairs.remove(1, 2).unwrap(), 1);
+    assert_eq!(pairs.remove(1, 2).unwrap(), 0);
+    assert_eq!(pairs.remove_right(3).unwrap(), 2);
+    assert_eq!(pairs.remove_left(5).unwrap(), 1);
+    assert_eq!(pairs.remove_left(5).unwrap(), 0);
+    assert_eq!(pairs.page_left(0, 10, &mut [(0, 0); 4]).unwrap(), 0);
+}
+
+/// Replacing a key's values only touches pairs that change.
+#[test]
+fn it_replaces_left() {
+    let pairs = new_table().unwrap();
+    pairs.insert(1, 2).unwrap();
+    pairs.insert(1, 3).unwrap();
+    pairs.insert(4, 3).unwrap();
+
+    assert_eq!(pairs.replace_left(1, vec![3, 5, 6]).unwrap(), 3);
+    let values: Vec<i64> = pairs.get(1).unwrap().map(Result::unwrap).collect();
+    assert_eq!(values, vec![3, 5, 6]);
+    assert_eq!(pairs.replace_left(1, vec![6, 5, 3]).unwrap(), 0);
+    assert_eq!(pairs.replace_left(1, vec![]).unwrap(), 3);
+    assert_eq!(pairs.get(1).unwrap().count(), 0);
+    assert_eq!(pairs.invert(3).unwrap().count(), 1);
+}
+
+/// Bulk inserts count new and already-present pairs.
+#[test]
+fn it_inserts_in_bulk() {
+    let pairs = new_table().unwrap();
+    pairs.insert(1, 2).unwrap();
+
+    let counts = pairs
+        .insert_bulk(vec![(1, 2), (1, 3), (4, 3), (1, 3)])
+        .unwrap();
+    assert_eq!(
+        counts,
+        InsertCounts {
+            inserted: 2,
+            existing: 2
+        }
+    );
+    assert_eq!(pairs.get(1).unwrap().count(), 2);
+    assert_eq!(pairs.invert(3).unwrap().count(), 2);
+    assert_eq!(
+        pairs.insert_bulk(Vec::new()).unwrap(),
+        InsertCounts::default()
+    );
+}
+
+/// Paging by cursor neither repeats nor skips pairs sharing a key.
+#[test]
+fn it_pages_pairs_by_cursor() {
+    let pairs = new_table().unwrap();
+    pairs
+        .insert_bulk(vec![(2, 1), (1, 3), (1, 2), (1, 1), (3, 1)])
+        .unwrap();
+
+    let mut seen = vec![];
+    let mut cursor: Option<Cursor> = None;
+    loop {
+        let page = pairs
+            .pairs_paged(PairColumn::Left, cursor.as_ref(), 2)
+            .unwrap();
+        seen.extend(page.items);
+        // Tokens survive a round trip through a client.
+        cursor = match page.next {
+            Some(next) => Some(next.to_string().parse().unwrap()),
+            None => break,
+        };
+    }
+    assert_eq!(seen, [(1, 1), (1, 2), (1, 3), (2, 1), (3, 1)]);
+
+    let page = pairs.pairs_paged(PairColumn::Right, None, 3).unwrap();
+    assert_eq!(page.items, [(1, 1), (2, 1), (3, 1)]);
+    let page = pairs
+        .pairs_paged(PairColumn::Right, page.next.as_ref(), 3)
+        .unwrap();
+    assert_eq!(page.items, [(1, 2), (1, 3)]);
+    assert_eq!(page.next, None);
+}
+
+/// Values and keys of one id page by cursor.
+#[test]
+fn it_pages_values_by_cursor() {
+    let pairs = new_table().unwrap();
+    pairs
+        .insert_bulk(vec![(1, 2), (1, 3), (1, 4), (5, 3)])
+        .unwrap();
+
+    let page = pairs.get_paged(1, None, 2).unwrap();
+    assert_eq!(page.items, [2, 3]);
+    let page = pairs.get_paged(1, page.next.as_ref(), 2).unwrap();
+    assert_eq!(page.items, [4]);
+    assert_eq!(page.next, None);
+
+    let page = pairs.invert_paged(3, None, 0).unwrap();
+    assert_eq!(page.items, [1]);
+    let page = pairs.invert_paged(3, page.next.as_ref(), 10).unwrap();
+    assert_eq!(page.items, [5]);
+}
+
+/// Cursors are only accepted by the listing that made them.
+#[test]
+fn error_on_foreign_cursor() {
+    let pairs = new_table().unwrap();
+    pairs.insert_bulk(vec![(1, 2), (1, 3), (4, 2)]).unwrap();
+    let next = pairs.get_paged(1, None, 1).unwrap().next.unwrap();
+    assert!(matches!(
+        pairs.get_paged(4, Some(&next), 1),
+        Err(NormalError::InvalidCursor(_))
+    ));
+    assert!(matches!(
+        pairs.invert_paged(1, Some(&next), 1),
+        Err(NormalError::InvalidCursor(_))
+    ));
+    assert!(matches!(
+        pairs.pairs_paged(PairColumn::Left, Some(&next), 1),
+        Err(NormalError::InvalidCursor(_))
+    ));
+}
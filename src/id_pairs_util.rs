@@ -1,4 +1,4 @@
-use normal::{IdPairs, NormalError};
+use normal::{IdPairs, Identifier, NormalError};
 use std::path::PathBuf;
 use std::process::exit;
 use structopt::StructOpt;
@@ -19,43 +19,48 @@ struct Opt {
     search: Option<i64>,
 
     #[structopt(short, long)]
-    left: String,
+    left: Identifier<'static>,
 
     #[structopt(short, long)]
-    right: String,
+    right: Identifier<'static>,
 
     #[structopt(short, long)]
-    table: String,
+    table: Identifier<'static>,
 }
 
 pub fn main() {
     let opt = Opt::from_args();
-    let pairs = IdPairs::new(
+    let pairs = match IdPairs::new(
         opt.db.as_os_str().to_str().unwrap(),
         opt.table.as_str(),
         opt.left.as_str(),
         opt.right.as_str(),
-    )
-    .unwrap();
+    ) {
+        Ok(pairs) => pairs,
+        Err(err) => {
+            println!("error: {}", err);
+            exit(1);
+        }
+    };
 
-    opt.insert.map(|insert_pair| {
+    if let Some(insert_pair) = opt.insert {
         let pair = parse_insertion(insert_pair);
         match pairs.insert(pair.0, pair.1) {
             Ok(_) => {}
             Err(e) => {
-                println!("error: {}", e.msg);
+                println!("error: {}", e);
                 exit(2)
             }
         }
-    });
+    }
 
-    opt.get
-        .map(|left| pairs.get(left))
-        .map(|got| print_results_or_fail(got));
+    if let Some(left) = opt.get {
+        print_results_or_fail(pairs.get(left));
+    }
 
-    opt.search
-        .map(|right| pairs.invert(right))
-        .map(|got| print_results_or_fail(got));
+    if let Some(right) = opt.search {
+        print_results_or_fail(pairs.invert(right));
+    }
 }
 
 fn parse_insertion(input: String) -> (i64, i64) {
@@ -64,17 +69,19 @@ fn parse_insertion(input: String) -> (i64, i64) {
         .take(2)
         .map(|s| s.parse::<i64>().unwrap())
         .collect();
-    (*tokens.get(0).unwrap(), *tokens.get(1).unwrap())
+    (*tokens.first().unwrap(), *tokens.get(1).unwrap())
 }
 
-fn print_results_or_fail(results: Result<impl Iterator<Item = i64>, NormalError>) -> () {
-    match results {
-        Ok(i) => {
-            i.for_each(|x| print!("{} ", x));
+fn print_results_or_fail(
+    results: Result<impl Iterator<Item = Result<i64, NormalError>>, NormalError>,
+) {
+    match results.and_then(|i| i.collect::<Result<Vec<i64>, NormalError>>()) {
+        Ok(ids) => {
+            ids.iter().for_each(|x| print!("{} ", x));
             println!();
         }
         Err(err) => {
-            println!("error: {}", err.msg);
+            println!("error: {}", err);
             exit(1);
         }
     }
@@ -0,0 +1,75 @@
+use crate::NormalError;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// A validated SQL identifier, e.g. a table or column name.
+///
+/// Identifiers are always quoted when spliced into SQL, so reserved words
+/// such as `values` and names containing punctuation are safe to use.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Identifier<'a> {
+    name: Cow<'a, str>,
+}
+
+impl<'a> Identifier<'a> {
+    /// Validate a table, column, or index name.
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Result<Identifier<'a>, NormalError> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(invalid(&name, "empty name"));
+        }
+        if name.contains('\0') {
+            return Err(invalid(&name, "contains NUL character"));
+        }
+        if name.to_ascii_lowercase().starts_with("sqlite_") {
+            return Err(invalid(&name, "reserved for internal use"));
+        }
+        Ok(Identifier { name })
+    }
+
+    /// Return the unquoted name.
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the name quoted for use in SQL.
+    pub fn quoted(&self) -> String {
+        quote(&self.name)
+    }
+
+    /// Derive the quoted name of the index over a column of a table.
+    pub(crate) fn index_name(table_name: &Identifier, column_name: &Identifier) -> String {
+        quote(&format!("idx_{}_{}", table_name.name, column_name.name))
+    }
+}
+
+impl<'a> fmt::Display for Identifier<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl FromStr for Identifier<'static> {
+    type Err = NormalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Identifier::new(s.to_string())
+    }
+}
+
+/// Double-quote an identifier, escaping embedded quotes.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn invalid(name: &str, reason: &'static str) -> NormalError {
+    NormalError::InvalidIdentifier {
+        name: name.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+#[path = "./identifier_test.rs"]
+mod identifier_test;
@@ -0,0 +1,53 @@
+use super::*;
+
+/// Ensure that ordinary names are accepted and quoted.
+#[test]
+fn it_quotes_names() {
+    let id = Identifier::new("names").unwrap();
+    assert_eq!(id.as_str(), "names");
+    assert_eq!(id.quoted(), "\"names\"");
+}
+
+/// Ensure that embedded quotes are escaped.
+#[test]
+fn it_escapes_quotes() {
+    let id = Identifier::new("x\"; DROP TABLE y; --").unwrap();
+    assert_eq!(id.quoted(), "\"x\"\"; DROP TABLE y; --\"");
+}
+
+/// Ensure that reserved words are accepted.
+#[test]
+fn it_accepts_keywords() {
+    assert!(Identifier::new("values").is_ok());
+}
+
+/// Reject empty names.
+#[test]
+fn it_rejects_empty() {
+    assert_eq!(
+        Identifier::new("").unwrap_err().to_string(),
+        "invalid identifier \"\": empty name"
+    );
+}
+
+/// Reject names with NUL characters.
+#[test]
+fn it_rejects_nul() {
+    assert!(matches!(
+        Identifier::new("na\0me"),
+        Err(NormalError::InvalidIdentifier { .. })
+    ));
+}
+
+/// Reject names reserved by SQLite.
+#[test]
+fn it_rejects_reserved_prefix() {
+    assert!(Identifier::new("SQLite_master").is_err());
+}
+
+/// Parse from a command-line argument.
+#[test]
+fn it_parses_from_str() {
+    let id: Identifier<'static> = "genres".parse().unwrap();
+    assert_eq!(id.as_str(), "genres");
+}
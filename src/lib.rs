@@ -14,69 +14,188 @@
 //! }
 //!
 //! assert_eq!(genres.search("%").unwrap().count(), 4);
-//! assert_eq!(genres.search("b%s").unwrap().next().unwrap(), (1, "blues".to_string()));
-//! assert_eq!(genres.search("p%").unwrap().next().unwrap(), (3, "punk".to_string()));
+//! assert_eq!(genres.search("b%s").unwrap().next().unwrap().unwrap(), (1, "blues".to_string()));
+//! assert_eq!(genres.search("p%").unwrap().next().unwrap().unwrap(), (3, "punk".to_string()));
+//! ```
+//!
+//! Tables in the same file may share a connection and transactions:
+//!
+//! ```
+//! use normal::Database;
+//!
+//! let db = Database::open(":memory:").unwrap();
+//! let genres = db.normal("genres", "genre").unwrap();
+//! let artists = db.normal("artists", "artist").unwrap();
+//! let genre_artists = db.id_pairs("genre_artists", "genre", "artist").unwrap();
+//!
+//! let tx = db.transaction().unwrap();
+//! let genre = genres.create("bluegrass").unwrap();
+//! let artist = artists.create("Bill Monroe").unwrap();
+//! genre_artists.insert(genre, artist).unwrap();
+//! tx.commit().unwrap();
 //! ```
 
-use sqlite::{Cursor, Value};
-use std::fmt;
+use cached_connection::CachedConnection;
+use parking_lot::ReentrantMutexGuard;
+use sqlite::{Connection, Value};
 
-#[derive(Clone, Debug)]
-pub struct NormalError {
-    pub msg: String,
+/// Iterate over query results, surfacing read failures as errors.
+///
+/// The iterator keeps the shared connection locked until it is dropped.
+struct SearchIterator<'a, T> {
+    // The cursor borrows the locked connection, so it is declared, and
+    // dropped, before the guard.
+    cursor: sqlite::Cursor<'a>,
+    f: fn(&[Value]) -> Result<T, NormalError>,
+    failed: bool,
+    _guard: ReentrantMutexGuard<'a, CachedConnection>,
 }
 
-impl fmt::Display for NormalError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "normalization: {}", self.msg)
+impl<'a, T> SearchIterator<'a, T> {
+    fn open(
+        guard: ReentrantMutexGuard<'a, CachedConnection>,
+        query: &str,
+        params: &[Value],
+        f: fn(&[Value]) -> Result<T, NormalError>,
+    ) -> Result<SearchIterator<'a, T>, NormalError> {
+        // SAFETY: the connection lives as long as its mutex, and the cursor
+        // borrowing it is dropped before the guard; see field order.
+        let locked = unsafe { &*(guard.conn() as *const Connection) };
+        let mut cursor = locked.prepare(query)?.cursor();
+        cursor.bind(params)?;
+        Ok(SearchIterator {
+            cursor,
+            f,
+            failed: false,
+            _guard: guard,
+        })
     }
 }
 
-struct SearchIterator<'a, T> {
-    cursor: Cursor<'a>,
-    f: fn(&[Value]) -> Option<T>,
-}
-
 impl<'a, T> Iterator for SearchIterator<'a, T> {
-    type Item = T;
+    type Item = Result<T, NormalError>;
 
-    fn next(&mut self) -> Option<T> {
+    fn next(&mut self) -> Option<Result<T, NormalError>> {
+        if self.failed {
+            return None;
+        }
         let f = self.f; // cannot pass borrowed value into closure.
         match self.cursor.next() {
-            Ok(row) => row.and_then(|r| (f)(r)),
-            _ => None,
+            Ok(row) => row.map(f),
+            Err(e) => {
+                self.failed = true; // the cursor cannot recover; stop after reporting.
+                Some(Err(NormalError::sqlite("failed to read search results", e)))
+            }
         }
     }
 }
 
-/// Boilerplate to safely unwrap sqlite error messages.
-macro_rules! unwrap_msg {
-    ($sql_err:expr) => {
-        $sql_err.message.unwrap_or("???".to_string())
-    };
+fn new_search_iterator<'a>(
+    conn: ReentrantMutexGuard<'a, CachedConnection>,
+    query: &str,
+    params: &[Value],
+) -> Result<SearchIterator<'a, i64>, NormalError> {
+    SearchIterator::open(conn, query, params, |row: &[Value]| read_integer(row, 0))
 }
 
-fn new_search_iterator<'a>(cursor: Cursor<'a>) -> SearchIterator<'a, i64> {
-    SearchIterator {
-        cursor: cursor,
-        f: |row: &[Value]| row[0].as_integer(),
-    }
+fn new_search_string_iterator<'a>(
+    conn: ReentrantMutexGuard<'a, CachedConnection>,
+    query: &str,
+    params: &[Value],
+) -> Result<SearchIterator<'a, (i64, String)>, NormalError> {
+    SearchIterator::open(conn, query, params, |row: &[Value]| {
+        Ok((read_integer(row, 0)?, read_string(row, 1)?))
+    })
+}
+
+/// Read an integer column, reporting unexpected types, e.g. NULL.
+fn read_integer(row: &[Value], i: usize) -> Result<i64, NormalError> {
+    row[i]
+        .as_integer()
+        .ok_or_else(|| NormalError::SchemaMismatch(format!("expected integer in column {}", i)))
 }
 
-fn new_search_string_iterator<'a>(cursor: Cursor<'a>) -> SearchIterator<'a, (i64, String)> {
-    SearchIterator {
-        cursor: cursor,
-        f: |row: &[Value]| {
-            Some((
-                row[0].as_integer().unwrap(),
-                row[1].as_string().unwrap().to_string(),
-            ))
-        },
+/// Read a text column, reporting unexpected types, e.g. NULL.
+fn read_string(row: &[Value], i: usize) -> Result<String, NormalError> {
+    row[i]
+        .as_string()
+        .map(|s| s.to_string())
+        .ok_or_else(|| NormalError::SchemaMismatch(format!("expected text in column {}", i)))
+}
+
+/// List the columns of a table, or nothing if it does not exist.
+fn table_columns(conn: &Connection, table_name: &Identifier) -> Result<Vec<String>, NormalError> {
+    let mut statement = conn.prepare(format!("PRAGMA table_info({})", table_name.quoted()))?;
+    let mut columns = vec![];
+    while statement
+        .next()
+        .map_err(|e| NormalError::sqlite(format!("cannot list columns of {}", table_name), e))?
+        == sqlite::State::Row
+    {
+        columns.push(statement.read::<String>(1)?);
     }
+    Ok(columns)
+}
+
+/// Check that an existing table has the columns, e.g. when reopening it
+/// with a misspelled column name, and return them as the table spells them.
+fn check_columns(
+    conn: &Connection,
+    table_name: &Identifier,
+    columns: &[&Identifier],
+) -> Result<Vec<Identifier<'static>>, NormalError> {
+    let existing = table_columns(conn, table_name)?;
+    columns
+        .iter()
+        .map(|column| {
+            match existing
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(column.as_str()))
+            {
+                Some(name) => Identifier::new(name.clone()),
+                None => Err(NormalError::SchemaMismatch(format!(
+                    "table {} has no column {}",
+                    table_name, column
+                ))),
+            }
+        })
+        .collect()
 }
 
+mod cached_connection;
+mod connection_pool;
+mod regexp;
+
+mod database;
+pub use database::Database;
+
+mod error;
+pub use error::NormalError;
+
+mod fuzzy;
+
+mod identifier;
+pub use identifier::Identifier;
+
+mod nonkey;
+pub use nonkey::{ColumnType, FromSql, NonKeyColumn, Record, ToSql};
+
+mod normalization;
+pub use normalization::{NormalizationPolicy, UnicodeForm};
+
+mod page;
+pub use page::{Cursor, Page};
+
+mod transaction;
+pub use transaction::Transaction;
+
 mod normal_model;
-pub use normal_model::Normal;
+pub use normal_model::{escape_like, CreateStatus, Normal, OnDelete, SearchMode};
 
 mod id_pairs_model;
-pub use id_pairs_model::IdPairs;
+pub use id_pairs_model::{IdPairs, InsertCounts, PairColumn};
+
+#[cfg(feature = "async")]
+mod async_model;
+#[cfg(feature = "async")]
+pub use async_model::{AsyncIdPairs, AsyncNormal};
@@ -0,0 +1,313 @@
+use crate::NormalError;
+use sqlite::{Type, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// SQL types of non-key columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+    Blob,
+}
+
+impl ColumnType {
+    /// The SQL name of the type.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+            ColumnType::Blob => "BLOB",
+        }
+    }
+
+    /// The type of a stored value, or `None` for NULL.
+    pub(crate) fn of(kind: Type) -> Option<ColumnType> {
+        match kind {
+            Type::Integer => Some(ColumnType::Integer),
+            Type::Float => Some(ColumnType::Real),
+            Type::String => Some(ColumnType::Text),
+            Type::Binary => Some(ColumnType::Blob),
+            Type::Null => None,
+        }
+    }
+
+    /// The name `typeof()` reports for values of this type.
+    fn typeof_name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Real => "real",
+            ColumnType::Text => "text",
+            ColumnType::Blob => "blob",
+        }
+    }
+
+    /// The default for NOT NULL columns added to existing rows.
+    fn zero(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "0",
+            ColumnType::Real => "0.0",
+            ColumnType::Text => "''",
+            ColumnType::Blob => "X''",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Declaration of a non-key column.
+///
+/// Writes of another type fail with a constraint error.  Columns that are
+/// not nullable hold zero or empty values until notated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonKeyColumn {
+    pub name: String,
+    pub column_type: ColumnType,
+    pub nullable: bool,
+}
+
+impl NonKeyColumn {
+    /// Declare a nullable column.
+    pub fn new(name: impl Into<String>, column_type: ColumnType) -> NonKeyColumn {
+        NonKeyColumn {
+            name: name.into(),
+            column_type,
+            nullable: true,
+        }
+    }
+
+    /// The column definition following the quoted name in `ADD COLUMN`.
+    pub(crate) fn definition(&self, quoted_name: &str) -> String {
+        let column_type = self.column_type;
+        if self.nullable {
+            format!(
+                "{} CHECK (typeof({}) IN ('{}', 'null'))",
+                column_type,
+                quoted_name,
+                column_type.typeof_name()
+            )
+        } else {
+            format!(
+                "{} NOT NULL DEFAULT {} CHECK (typeof({}) = '{}')",
+                column_type,
+                column_type.zero(),
+                quoted_name,
+                column_type.typeof_name()
+            )
+        }
+    }
+}
+
+/// A row's key and non-key values, read by `Normal::get_record`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub id: i64,
+    pub key: String,
+    pub nonkeys: BTreeMap<String, Value>,
+}
+
+impl Record {
+    /// Get a non-key value, as `Normal::get_nonkey` would.
+    pub fn get<T: FromSql>(&self, column_name: &str) -> Result<T, NormalError> {
+        match self.nonkeys.get(column_name) {
+            Some(value) => read_nonkey(value.clone(), column_name, self.id),
+            None => Err(NormalError::SchemaMismatch(format!(
+                "missing non-key column {}",
+                column_name
+            ))),
+        }
+    }
+}
+
+/// Convert a stored non-key value, reporting NULL as uninitialized.
+pub(crate) fn read_nonkey<T: FromSql>(
+    value: Value,
+    column_name: &str,
+    id: i64,
+) -> Result<T, NormalError> {
+    let found = ColumnType::of(value.kind());
+    match (T::from_sql(value), found) {
+        (Some(value), _) => Ok(value),
+        (None, None) => Err(NormalError::NotFound(format!(
+            "uninitialized non-key column {} for id {}",
+            column_name, id
+        ))),
+        (None, Some(found)) => Err(NormalError::TypeMismatch {
+            column: column_name.to_string(),
+            expected: T::COLUMN_TYPE,
+            found,
+        }),
+    }
+}
+
+/// Values that may be written to non-key columns.
+pub trait ToSql {
+    fn to_sql(&self) -> Value;
+}
+
+/// Values that may be read from non-key columns.
+pub trait FromSql: Sized {
+    /// The column type the value is read from.
+    const COLUMN_TYPE: ColumnType;
+
+    /// Convert a stored value, or return `None` if it has another type.
+    fn from_sql(value: Value) -> Option<Self>;
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_sql(&self) -> Value {
+        (**self).to_sql()
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_sql(&self) -> Value {
+        match self {
+            Some(value) => value.to_sql(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl ToSql for i64 {
+    fn to_sql(&self) -> Value {
+        Value::Integer(*self)
+    }
+}
+
+impl ToSql for i32 {
+    fn to_sql(&self) -> Value {
+        Value::Integer(i64::from(*self))
+    }
+}
+
+impl ToSql for bool {
+    fn to_sql(&self) -> Value {
+        Value::Integer(i64::from(*self))
+    }
+}
+
+impl ToSql for f64 {
+    fn to_sql(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+impl ToSql for str {
+    fn to_sql(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl ToSql for String {
+    fn to_sql(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl ToSql for [u8] {
+    fn to_sql(&self) -> Value {
+        Value::Binary(self.to_vec())
+    }
+}
+
+impl ToSql for Vec<u8> {
+    fn to_sql(&self) -> Value {
+        Value::Binary(self.clone())
+    }
+}
+
+impl<T: FromSql> FromSql for Option<T> {
+    const COLUMN_TYPE: ColumnType = T::COLUMN_TYPE;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_sql(value).map(Some),
+        }
+    }
+}
+
+impl FromSql for i64 {
+    const COLUMN_TYPE: ColumnType = ColumnType::Integer;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for i32 {
+    const COLUMN_TYPE: ColumnType = ColumnType::Integer;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => i32::try_from(value).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for bool {
+    const COLUMN_TYPE: ColumnType = ColumnType::Integer;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(value != 0),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for f64 {
+    const COLUMN_TYPE: ColumnType = ColumnType::Real;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Float(value) => Some(value),
+            Value::Integer(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for String {
+    const COLUMN_TYPE: ColumnType = ColumnType::Text;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSql for Vec<u8> {
+    const COLUMN_TYPE: ColumnType = ColumnType::Blob;
+
+    fn from_sql(value: Value) -> Option<Self> {
+        match value {
+            Value::Binary(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./nonkey_test.rs"]
+mod nonkey_test;
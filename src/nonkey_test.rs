@@ -0,0 +1,34 @@
+use super::*;
+
+/// Column definitions constrain the stored type.
+#[test]
+fn it_defines_columns() {
+    let count = NonKeyColumn::new("count", ColumnType::Integer);
+    assert_eq!(
+        count.definition("\"count\""),
+        "INTEGER CHECK (typeof(\"count\") IN ('integer', 'null'))"
+    );
+    let digest = NonKeyColumn {
+        nullable: false,
+        ..NonKeyColumn::new("digest", ColumnType::Blob)
+    };
+    assert_eq!(
+        digest.definition("\"digest\""),
+        "BLOB NOT NULL DEFAULT X'' CHECK (typeof(\"digest\") = 'blob')"
+    );
+}
+
+/// Values convert to and from their SQL types.
+#[test]
+fn it_converts_values() {
+    assert_eq!(true.to_sql(), Value::Integer(1));
+    assert_eq!("a".to_sql(), Value::String("a".to_string()));
+    assert_eq!(None::<f64>.to_sql(), Value::Null);
+
+    assert_eq!(i32::from_sql(Value::Integer(7)), Some(7));
+    assert_eq!(i32::from_sql(Value::Integer(i64::MAX)), None);
+    assert_eq!(f64::from_sql(Value::Integer(2)), Some(2.0));
+    assert_eq!(i64::from_sql(Value::Float(2.0)), None);
+    assert_eq!(String::from_sql(Value::Null), None);
+    assert_eq!(Option::<String>::from_sql(Value::Null), Some(None));
+}
@@ -1,323 +1,1364 @@
-use crate::{new_search_string_iterator, NormalError};
-use sqlite::{Connection, State, Value};
-use std::convert::TryInto;
+use crate::connection_pool::SharedConnection;
+use crate::fuzzy::{trigram_query, trigram_similarity};
+use crate::nonkey::read_nonkey;
+use crate::page::CursorKind;
+use crate::{
+    check_columns, new_search_string_iterator, table_columns, ColumnType, Cursor, Database,
+    FromSql, IdPairs, Identifier, NonKeyColumn, NormalError, NormalizationPolicy, Page, PairColumn,
+    Record, ToSql, Transaction,
+};
+use parking_lot::Mutex;
+use sqlite::{Connection, State, Statement, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Whether `Normal::create_with_status` inserted a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreateStatus {
+    /// The value was inserted with a new id.
+    Created,
+    /// The value was already present.
+    Existing,
+}
+
+/// How `Normal::delete` treats pairs referring to the deleted id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Refuse to delete ids still referenced by registered pairs tables.
+    Restrict,
+    /// Remove the referring pairs along with the id.
+    Cascade,
+}
+
+/// How `Normal::search_with_mode` matches values against a search string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchMode {
+    /// SQL `LIKE` with `%` and `_` wildcards, ignoring ASCII case.
+    Like,
+    /// SQL `LIKE` where the escape character makes the following `%`, `_`
+    /// or escape character literal, e.g. `100\%%` with `'\\'`.  The
+    /// pattern is not normalized, so that the escape character survives;
+    /// only ASCII case is ignored.
+    LikeEscape(char),
+    /// SQLite `GLOB` with `*`, `?` and `[...]` wildcards, matching case.
+    Glob,
+    /// The whole value.
+    Exact,
+    /// Values starting with the search string.
+    Prefix,
+    /// Values containing the search string literally, ignoring ASCII case.
+    Contains,
+    /// A regular expression in `regex` crate syntax, matching anywhere in
+    /// the value unless anchored.
+    Regex,
+}
+
+impl SearchMode {
+    /// The condition matching a column against parameter 1.  Parameter 4
+    /// holds the bound above prefixes, or the escape character.
+    fn condition(self, column: &str) -> String {
+        match self {
+            SearchMode::Like => format!("{} LIKE ?1", column),
+            SearchMode::LikeEscape(_) | SearchMode::Contains => {
+                format!("{} LIKE ?1 ESCAPE ?4", column)
+            }
+            SearchMode::Glob => format!("{} GLOB ?1", column),
+            SearchMode::Exact => format!("{} = ?1", column),
+            SearchMode::Prefix => format!("({0} >= ?1 AND {0} < ?4)", column),
+            SearchMode::Regex => format!("{} REGEXP ?1", column),
+        }
+    }
+}
+
+impl FromStr for SearchMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<SearchMode, String> {
+        match mode {
+            "like" => Ok(SearchMode::Like),
+            "glob" => Ok(SearchMode::Glob),
+            "exact" => Ok(SearchMode::Exact),
+            "prefix" => Ok(SearchMode::Prefix),
+            "contains" => Ok(SearchMode::Contains),
+            "regex" => Ok(SearchMode::Regex),
+            _ => Err(format!(
+                "unknown search mode {:?}: expected like, glob, exact, prefix, contains or regex",
+                mode
+            )),
+        }
+    }
+}
+
+/// Escape `%`, `_` and the escape character itself, so that `LIKE` with
+/// `SearchMode::LikeEscape(escape)` matches the value literally.
+pub fn escape_like(value: &str, escape: char) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '%' || c == '_' || c == escape {
+            escaped.push(escape);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
 
 /// Structure for maintaining normalized fields.
-pub struct Normal<'a> {
-    table_name: &'a str,
-    column_name: &'a str,
-    conn: Connection,
+///
+/// Tables own their names and may be kept in long-lived state or moved
+/// between threads.
+pub struct Normal {
+    table_name: Identifier<'static>,
+    column_name: Identifier<'static>,
+    alias_table: Identifier<'static>,
+    fts_table: Identifier<'static>,
+    trigram_table: Identifier<'static>,
+    display_column: Option<Identifier<'static>>,
+    policy: NormalizationPolicy,
+    references: Mutex<Vec<(IdPairs, PairColumn)>>,
+    conn: SharedConnection,
 }
 
-impl<'a> Normal<'a> {
+impl Normal {
     /// Create a normalization table from a file name.
-    pub fn new<'b>(
+    pub fn new(
         file_name: &str,
-        table_name: &'b str,
-        column_name: &'b str,
-    ) -> Result<Normal<'b>, sqlite::Error> {
-        Ok(Normal {
-            table_name: table_name,
-            column_name: column_name,
-            conn: open(file_name, table_name, column_name)?,
-        })
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Normal, NormalError> {
+        Database::open(file_name)?.normal(table_name, column_name)
     }
 
     /// Create a normalization table with extra non-key columns.
-    pub fn new_with_nonkeys<'b, T: AsRef<str>>(
+    pub fn new_with_nonkeys<T: AsRef<str>>(
         file_name: &str,
-        table_name: &'b str,
-        column_name: &'b str,
+        table_name: &str,
+        column_name: &str,
         nonkeys: impl Iterator<Item = T>,
-    ) -> Result<Normal<'b>, sqlite::Error> {
+    ) -> Result<Normal, NormalError> {
+        Database::open(file_name)?.normal_with_nonkeys(table_name, column_name, nonkeys)
+    }
+
+    /// Create a normalization table with typed non-key columns.
+    pub fn new_with_typed_nonkeys(
+        file_name: &str,
+        table_name: &str,
+        column_name: &str,
+        nonkeys: impl IntoIterator<Item = NonKeyColumn>,
+    ) -> Result<Normal, NormalError> {
+        Database::open(file_name)?.normal_with_typed_nonkeys(table_name, column_name, nonkeys)
+    }
+
+    /// Create a normalization table normalizing values with a policy, e.g.
+    /// to treat "Jazz" and "jazz" as the same value.
+    pub fn new_with_policy(
+        file_name: &str,
+        table_name: &str,
+        column_name: &str,
+        policy: NormalizationPolicy,
+    ) -> Result<Normal, NormalError> {
+        Database::open(file_name)?.normal_with_policy(table_name, column_name, policy)
+    }
+
+    /// Create the table, if necessary, on a shared connection.
+    pub(crate) fn open(
+        conn: SharedConnection,
+        table_name: &str,
+        column_name: &str,
+        nonkeys: impl Iterator<Item = NonKeyColumn>,
+        policy: NormalizationPolicy,
+    ) -> Result<Normal, NormalError> {
+        let table_name = Identifier::new(table_name.to_string())?;
+        let column_name = Identifier::new(column_name.to_string())?;
+        let alias_table = Identifier::new(format!("{}_aliases", table_name))?;
+        let fts_table = Identifier::new(format!("{}_fts", table_name))?;
+        let trigram_table = Identifier::new(format!("{}_trigrams", table_name))?;
+        let display_column = match &policy.display_column {
+            Some(column) => Some(Identifier::new(column.clone())?),
+            None => None,
+        };
+        let column_name = {
+            let conn = conn.writer();
+            let column_name = create_table(conn.conn(), &table_name, &column_name)?;
+            create_alias_table(conn.conn(), &alias_table)?;
+            add_nonkeys(conn.conn(), &table_name, nonkeys)?;
+            add_nonkeys(
+                conn.conn(),
+                &table_name,
+                display_column
+                    .iter()
+                    .map(|column| NonKeyColumn::new(column.as_str(), ColumnType::Text)),
+            )?;
+            column_name
+        };
         Ok(Normal {
-            table_name: table_name,
-            column_name: column_name,
-            conn: open_with_nonkeys(file_name, table_name, column_name, nonkeys)?,
+            table_name,
+            column_name,
+            alias_table,
+            fts_table,
+            trigram_table,
+            display_column,
+            policy,
+            references: Mutex::new(vec![]),
+            conn,
         })
     }
 
     /// Insert a new keyword/token and return the associated id.
+    /// The value is normalized according to the table's policy.
     pub fn create(&self, value: &str) -> Result<i64, NormalError> {
+        self.create_with_status(value).map(|(id, _)| id)
+    }
+
+    /// Insert a new keyword/token and return the associated id, along with
+    /// whether the value was newly inserted.  New values take one statement
+    /// where SQLite supports `RETURNING`; existing values and aliases take a
+    /// second one to find their id.
+    pub fn create_with_status(&self, value: &str) -> Result<(i64, CreateStatus), NormalError> {
+        let key = self.policy.apply(value);
+        let _lock = self.conn.writer(); // keep the insertion and lookup together.
+        let inserted = if supports_returning() {
+            self.insert_returning(&key, value)?
+        } else {
+            self.insert_or_ignore(&key, value)?
+        };
+        match inserted {
+            Some(id) => Ok((id, CreateStatus::Created)),
+            None => match self.find_or_alias(&key)? {
+                Some(id) => Ok((id, CreateStatus::Existing)),
+                None => Err(NormalError::NotFound(format!(
+                    "failed to insert/find insertion for value: {}",
+                    value
+                ))),
+            },
+        }
+    }
+
+    /// Name the columns written on insertion, along with their parameters.
+    /// Rows are only selected for values that are neither present nor
+    /// aliases, since conflicting inserts use up an id under AUTOINCREMENT.
+    fn insert_columns(&self) -> String {
+        let columns = match &self.display_column {
+            Some(display_column) => format!(
+                "({}, {}) SELECT ?1, ?2",
+                self.column_name.quoted(),
+                display_column.quoted()
+            ),
+            None => format!("({}) SELECT ?1", self.column_name.quoted()),
+        };
+        format!(
+            "{} WHERE NOT EXISTS (SELECT 1 FROM {} WHERE {} = ?1)
+                AND NOT EXISTS (SELECT 1 FROM {} WHERE alias = ?1)",
+            columns,
+            self.table_name.quoted(),
+            self.column_name.quoted(),
+            self.alias_table.quoted()
+        )
+    }
+
+    /// Bind the normalized key and, if kept, the original spelling.
+    fn bind_insert(&self, statement: &mut Statement, key: &str, value: &str) -> sqlite::Result<()> {
+        statement.bind(1, key)?;
+        if self.display_column.is_some() {
+            statement.bind(2, value)?;
+        }
+        Ok(())
+    }
+
+    /// Insert a value, returning its id in the same statement, or nothing if
+    /// the value already exists, since `RETURNING` only reports new rows.
+    fn insert_returning(&self, key: &str, value: &str) -> Result<Option<i64>, NormalError> {
         let query = format!(
-            "INSERT OR IGNORE INTO {} ({}) VALUES (?);",
-            self.table_name, self.column_name
+            "INSERT INTO {} {} ON CONFLICT DO NOTHING RETURNING rowid;",
+            self.table_name.quoted(),
+            self.insert_columns()
         );
-        let mut statement = self.conn.prepare(query).unwrap();
-        statement.bind(1, value).unwrap();
-        statement.next().unwrap();
+        self.conn.writer().with_statement(&query, |statement| {
+            self.bind_insert(statement, key, value)?;
+            match statement.next() {
+                Ok(State::Row) => Ok(Some(statement.read::<i64>(0)?)),
+                Ok(State::Done) => Ok(None),
+                Err(e) => Err(NormalError::sqlite(
+                    format!("failed to insert value {}", value),
+                    e,
+                )),
+            }
+        })
+    }
 
+    /// Insert a value for SQLite versions without RETURNING, reading the id
+    /// from the connection if a row was added.
+    fn insert_or_ignore(&self, key: &str, value: &str) -> Result<Option<i64>, NormalError> {
         let query = format!(
-            "SELECT rowid FROM {} WHERE {} = ?",
-            self.table_name, self.column_name
+            "INSERT OR IGNORE INTO {} {};",
+            self.table_name.quoted(),
+            self.insert_columns()
         );
-        let mut statement = self.conn.prepare(query).unwrap();
-        statement.bind(1, value).unwrap();
-        match statement.next() {
-            Ok(State::Row) => Ok(statement.read::<i64>(0).unwrap()),
-            Ok(State::Done) => Err(NormalError {
-                msg: format!("failed to insert/find insertion for value: {}", value),
-            }),
-            Err(e) => Err(NormalError {
-                msg: format!("failed to insert value {}: {}", value, unwrap_msg!(e)),
-            }),
+        let conn = self.conn.writer();
+        conn.with_statement(&query, |statement| {
+            self.bind_insert(statement, key, value)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("failed to insert value {}", value), e))
+        })?;
+        if conn.conn().changes() == 0 {
+            return Ok(None);
         }
+        conn.with_statement("SELECT last_insert_rowid();", |statement| {
+            statement.next()?;
+            Ok(Some(statement.read::<i64>(0)?))
+        })
     }
 
-    /// Retrieve the keyword/token with the id.
-    pub fn get(&self, id: i64) -> Result<String, NormalError> {
+    /// Find the id of an existing value without inserting it.
+    /// Aliases resolve to the id of their value.
+    pub fn lookup(&self, value: &str) -> Result<Option<i64>, NormalError> {
+        self.find_or_alias(&self.policy.apply(value))
+    }
+
+    /// Find the id of a normalized value or alias in one query.
+    fn find_or_alias(&self, key: &str) -> Result<Option<i64>, NormalError> {
         let query = format!(
-            "SELECT {} FROM {} WHERE rowid = ?",
-            self.column_name, self.table_name
+            "
+                SELECT rowid FROM {} WHERE {} = ?1
+                UNION ALL SELECT id FROM {} WHERE alias = ?1
+                LIMIT 1
+                ",
+            self.table_name.quoted(),
+            self.column_name.quoted(),
+            self.alias_table.quoted()
         );
-        let mut statement = self.conn.prepare(query).unwrap();
-        statement.bind(1, id).unwrap();
-        match statement.next() {
-            Ok(State::Row) => Ok(statement.read::<String>(0).unwrap()),
-            Ok(State::Done) => Err(NormalError {
-                msg: format!("missing key: {}", id),
-            }),
-            Err(_) => Err(NormalError {
-                msg: format!("cannot get key: {}", id),
-            }),
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, key)?;
+            match statement.next() {
+                Ok(State::Row) => Ok(Some(statement.read::<i64>(0)?)),
+                Ok(State::Done) => Ok(None),
+                Err(e) => Err(NormalError::sqlite(
+                    format!("cannot look up value {}", key),
+                    e,
+                )),
+            }
+        })
+    }
+
+    /// Register another spelling for the value with the id, so that
+    /// `create` and lookups resolve it to the id.  Spellings already taken
+    /// by another value or alias are reported as conflicts.
+    pub fn add_alias(&self, id: i64, alias: &str) -> Result<(), NormalError> {
+        let key = self.policy.apply(alias);
+        let tx = self.transaction()?;
+        self.get(id)?;
+        let holder = self.find_or_alias(&key)?;
+        match holder {
+            Some(holder) if holder == id => return tx.commit(),
+            Some(holder) => {
+                return Err(NormalError::Conflict {
+                    value: alias.to_string(),
+                    id: holder,
+                })
+            }
+            None => (),
         }
+        let query = format!(
+            "INSERT INTO {} (alias, id) VALUES (?, ?);",
+            self.alias_table.quoted()
+        );
+        self.conn.writer().with_statement(&query, |statement| {
+            statement.bind(1, key.as_ref())?;
+            statement.bind(2, id)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("cannot add alias {}", alias), e))
+        })?;
+        tx.commit()
+    }
+
+    /// Remove an alias, returning whether it existed.
+    pub fn remove_alias(&self, alias: &str) -> Result<bool, NormalError> {
+        let key = self.policy.apply(alias);
+        let query = format!("DELETE FROM {} WHERE alias = ?;", self.alias_table.quoted());
+        let conn = self.conn.writer();
+        conn.with_statement(&query, |statement| {
+            statement.bind(1, key.as_ref())?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("cannot remove alias {}", alias), e))
+        })?;
+        Ok(conn.conn().changes() > 0)
     }
 
-    /// Retrieve values for multiple indices, reusing a prepared statement.
-    pub fn get_bulk(
+    /// List the aliases of the value with the id.
+    pub fn aliases(&self, id: i64) -> Result<Vec<String>, NormalError> {
+        let query = format!(
+            "SELECT alias FROM {} WHERE id = ? ORDER BY alias",
+            self.alias_table.quoted()
+        );
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            let mut aliases = vec![];
+            while statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("cannot list aliases of {}", id), e))?
+                == State::Row
+            {
+                aliases.push(statement.read::<String>(0)?);
+            }
+            Ok(aliases)
+        })
+    }
+
+    /// Find the ids of existing values in order, with `None` for values
+    /// not in the table.  Nothing is inserted.
+    pub fn lookup_bulk<T: AsRef<str>>(
         &self,
-        ids: &Vec<i64>,
-        dest: &mut Vec<(i64, String)>,
-    ) -> Result<usize, NormalError> {
+        values: &[T],
+    ) -> Result<Vec<Option<i64>>, NormalError> {
         let query = format!(
-            "SELECT rowid, {} FROM {} WHERE rowid = ?",
-            self.column_name, self.table_name
+            "SELECT {}, rowid FROM {} WHERE {} IN ({})",
+            self.column_name.quoted(),
+            self.table_name.quoted(),
+            self.column_name.quoted(),
+            vec!["?"; BULK_BATCH].join(", ")
         );
-        let mut statement = self.conn.prepare(query).unwrap();
+        let keys: Vec<Cow<str>> = values
+            .iter()
+            .map(|value| self.policy.apply(value.as_ref()))
+            .collect();
+        let mut found: HashMap<String, i64> = HashMap::with_capacity(keys.len());
+        self.query_batched(
+            &query,
+            &keys,
+            |statement, i, key| statement.bind(i, key.as_ref()),
+            |statement| {
+                found.insert(statement.read::<String>(0)?, statement.read::<i64>(1)?);
+                Ok(())
+            },
+        )?;
+        let missing: Vec<&str> = keys
+            .iter()
+            .map(|key| key.as_ref())
+            .filter(|key| !found.contains_key(*key))
+            .collect();
+        if !missing.is_empty() {
+            let query = format!(
+                "SELECT alias, id FROM {} WHERE alias IN ({})",
+                self.alias_table.quoted(),
+                vec!["?"; BULK_BATCH].join(", ")
+            );
+            self.query_batched(
+                &query,
+                &missing,
+                |statement, i, key| statement.bind(i, *key),
+                |statement| {
+                    found.insert(statement.read::<String>(0)?, statement.read::<i64>(1)?);
+                    Ok(())
+                },
+            )?;
+        }
+        Ok(keys
+            .iter()
+            .map(|key| found.get(key.as_ref()).copied())
+            .collect())
+    }
 
-        let mut count: usize = 0;
-        let limit = std::cmp::min(ids.len(), dest.len());
-        let mut i = 0;
-        while i < limit {
-            let key = ids[i];
-            statement.bind(1, key).unwrap();
-            match statement.next() {
-                Ok(State::Row) => {
-                    dest[i] = (key, statement.read::<String>(1).unwrap());
-                    count += 1;
+    /// Insert keywords/tokens in one transaction and return their ids in
+    /// order.  On error, none of the values are inserted.
+    pub fn create_bulk<T: AsRef<str>>(
+        &self,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<i64>, NormalError> {
+        let tx = self.transaction()?;
+        let ids = values
+            .into_iter()
+            .map(|value| self.create(value.as_ref()))
+            .collect::<Result<Vec<i64>, NormalError>>()?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    /// Begin a transaction, rolled back unless committed.
+    pub fn transaction(&self) -> Result<Transaction<'_>, NormalError> {
+        Transaction::begin(self.conn.writer())
+    }
+
+    /// Register a column of a pairs table as holding ids from this table,
+    /// so that `merge` and `delete` keep it consistent.  The pairs table
+    /// must come from the same `Database`, so that both are changed in one
+    /// transaction.
+    pub fn add_reference(&self, pairs: &IdPairs, column: PairColumn) -> Result<(), NormalError> {
+        if !pairs.shares_connection(&self.conn) {
+            return Err(NormalError::SchemaMismatch(format!(
+                "pairs table does not share a connection with {}",
+                self.table_name
+            )));
+        }
+        self.references.lock().push((pairs.clone(), column));
+        Ok(())
+    }
+
+    /// Merge a duplicate value into another: the loser's spelling and
+    /// aliases become aliases of the winner, and registered pairs tables
+    /// refer to the winner instead.  The loser's non-key values are dropped.
+    /// The loser's id is retired, and not given to values created later,
+    /// except in tables created before ids were declared, which may reuse
+    /// the highest id.
+    pub fn merge(&self, winner: i64, loser: i64) -> Result<(), NormalError> {
+        if winner == loser {
+            return Ok(());
+        }
+        let tx = self.transaction()?;
+        self.get(winner)?;
+        let key = self.get(loser)?;
+        let move_aliases = format!(
+            "UPDATE {} SET id = ? WHERE id = ?;",
+            self.alias_table.quoted()
+        );
+        let delete = format!("DELETE FROM {} WHERE rowid = ?;", self.table_name.quoted());
+        let add_alias = format!(
+            "INSERT INTO {} (alias, id) VALUES (?, ?);",
+            self.alias_table.quoted()
+        );
+        let context = || format!("cannot merge {} into {}", loser, winner);
+        let conn = self.conn.writer();
+        conn.with_statement(&move_aliases, |statement| {
+            statement.bind(1, winner)?;
+            statement.bind(2, loser)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(context(), e))
+        })?;
+        conn.with_statement(&delete, |statement| {
+            statement.bind(1, loser)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(context(), e))
+        })?;
+        conn.with_statement(&add_alias, |statement| {
+            statement.bind(1, key.as_str())?;
+            statement.bind(2, winner)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(context(), e))
+        })?;
+        for (pairs, column) in self.references.lock().iter() {
+            pairs.replace_id(*column, loser, winner)?;
+        }
+        tx.commit()
+    }
+
+    /// Undo a merge by turning an alias back into a value of its own,
+    /// returning the new id.  Pairs are not split, since it is unknown which
+    /// belong to the alias.
+    pub fn split(&self, alias: &str) -> Result<i64, NormalError> {
+        let tx = self.transaction()?;
+        if !self.remove_alias(alias)? {
+            return Err(NormalError::NotFound(format!("missing alias: {}", alias)));
+        }
+        let id = self.create(alias)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Change the spelling of the value with the id, e.g. to fix a typo.
+    /// Spellings taken by another value or alias are reported as conflicts.
+    pub fn rename(&self, id: i64, new_value: &str) -> Result<(), NormalError> {
+        let key = self.policy.apply(new_value);
+        let tx = self.transaction()?;
+        self.get(id)?;
+        let holder = self.find_or_alias(&key)?;
+        match holder {
+            Some(holder) if holder != id => {
+                return Err(NormalError::Conflict {
+                    value: new_value.to_string(),
+                    id: holder,
+                })
+            }
+            Some(_) => {
+                self.remove_alias(new_value)?; // the alias becomes the value.
+            }
+            None => (),
+        }
+        let query = match &self.display_column {
+            Some(display_column) => format!(
+                "UPDATE {} SET {} = ?, {} = ? WHERE rowid = ?;",
+                self.table_name.quoted(),
+                self.column_name.quoted(),
+                display_column.quoted()
+            ),
+            None => format!(
+                "UPDATE {} SET {} = ? WHERE rowid = ?;",
+                self.table_name.quoted(),
+                self.column_name.quoted()
+            ),
+        };
+        self.conn.writer().with_statement(&query, |statement| {
+            self.bind_insert(statement, &key, new_value)?;
+            let id_index = if self.display_column.is_some() { 3 } else { 2 };
+            statement.bind(id_index, id)?;
+            statement
+                .next()
+                .map_err(|e| NormalError::sqlite(format!("cannot rename {}", id), e))
+        })?;
+        tx.commit()
+    }
+
+    /// Delete the value with the id along with its aliases.  Pairs in
+    /// registered tables referring to the id are removed or prevent the
+    /// deletion, depending on `on_delete`.  The id is retired like that of
+    /// a merged value.
+    pub fn delete(&self, id: i64, on_delete: OnDelete) -> Result<(), NormalError> {
+        let tx = self.transaction()?;
+        self.get(id)?;
+        for (pairs, column) in self.references.lock().iter() {
+            match on_delete {
+                OnDelete::Restrict => {
+                    if pairs.contains_id(*column, id)? {
+                        return Err(NormalError::Referenced {
+                            id,
+                            table: pairs.table_name().to_string(),
+                        });
+                    }
                 }
-                Ok(State::Done) => (),
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("get_bulk failed at {}: {}:", key, unwrap_msg!(e)),
-                    });
+                OnDelete::Cascade => {
+                    match column {
+                        PairColumn::Left => pairs.remove_left(id)?,
+                        PairColumn::Right => pairs.remove_right(id)?,
+                    };
                 }
-            };
-            i += 1;
-            statement.reset().unwrap();
+            }
+        }
+        let delete_aliases = format!("DELETE FROM {} WHERE id = ?;", self.alias_table.quoted());
+        let delete = format!("DELETE FROM {} WHERE rowid = ?;", self.table_name.quoted());
+        let conn = self.conn.writer();
+        for query in [delete_aliases, delete].iter() {
+            conn.with_statement(query, |statement| {
+                statement.bind(1, id)?;
+                statement
+                    .next()
+                    .map_err(|e| NormalError::sqlite(format!("cannot delete {}", id), e))
+            })?;
         }
-        Ok(count)
+        tx.commit()
+    }
+
+    /// Retrieve the keyword/token with the id.
+    pub fn get(&self, id: i64) -> Result<String, NormalError> {
+        let query = format!(
+            "SELECT {} FROM {} WHERE rowid = ?",
+            self.column_name.quoted(),
+            self.table_name.quoted()
+        );
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            match statement.next() {
+                Ok(State::Row) => Ok(statement.read::<String>(0)?),
+                Ok(State::Done) => Err(NormalError::NotFound(format!("missing key: {}", id))),
+                Err(e) => Err(NormalError::sqlite(format!("cannot get key {}", id), e)),
+            }
+        })
+    }
+
+    /// Retrieve the spelling of the keyword/token with the id as first
+    /// created, if the table keeps a display column.
+    pub fn get_display(&self, id: i64) -> Result<String, NormalError> {
+        let display_column = match &self.display_column {
+            Some(display_column) => display_column,
+            None => return self.get(id),
+        };
+        let query = format!(
+            "SELECT COALESCE({}, {}) FROM {} WHERE rowid = ?",
+            display_column.quoted(),
+            self.column_name.quoted(),
+            self.table_name.quoted()
+        );
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            match statement.next() {
+                Ok(State::Row) => Ok(statement.read::<String>(0)?),
+                Ok(State::Done) => Err(NormalError::NotFound(format!("missing key: {}", id))),
+                Err(e) => Err(NormalError::sqlite(format!("cannot get key {}", id), e)),
+            }
+        })
+    }
+
+    /// Retrieve the values for multiple indices in order, with `None` for
+    /// missing ids.  Ids are looked up in batches, one query per batch.
+    pub fn get_bulk(&self, ids: &[i64]) -> Result<Vec<Option<String>>, NormalError> {
+        let query = format!(
+            "SELECT rowid, {} FROM {} WHERE rowid IN ({})",
+            self.column_name.quoted(),
+            self.table_name.quoted(),
+            vec!["?"; BULK_BATCH].join(", ")
+        );
+        let mut found: HashMap<i64, String> = HashMap::with_capacity(ids.len());
+        self.query_batched(
+            &query,
+            ids,
+            |statement, i, id| statement.bind(i, *id),
+            |statement| {
+                found.insert(statement.read::<i64>(0)?, statement.read::<String>(1)?);
+                Ok(())
+            },
+        )?;
+        Ok(ids.iter().map(|id| found.get(id).cloned()).collect())
     }
 
     /// Compute the non-key/notation column names.
-    pub fn get_nonkeys(&'a self) -> Result<Vec<String>, NormalError> {
-        let query = format!("PRAGMA table_info({})", self.table_name);
-        let mut statement = self.conn.prepare(query).unwrap();
+    pub fn get_nonkeys(&self) -> Result<Vec<String>, NormalError> {
+        let query = format!("PRAGMA table_info({})", self.table_name.quoted());
+        let conn = self.conn.reader();
+        let mut statement = conn.conn().prepare(query)?;
         let mut nonkeys: Vec<String> = vec![];
         loop {
             match statement.next() {
                 Ok(State::Row) => {
-                    let column = statement.read::<String>(1).unwrap();
-                    if column != self.column_name {
-                        nonkeys.push(column.to_string());
+                    let column = statement.read::<String>(1)?;
+                    if column != self.column_name.as_str() && column != ID_COLUMN {
+                        nonkeys.push(column);
                     }
                 }
                 Ok(State::Done) => return Ok(nonkeys),
-                Err(e) => {
-                    return Err(NormalError {
-                        msg: format!("cannot get non-key columns: {}", unwrap_msg!(e)),
-                    })
-                }
+                Err(e) => return Err(NormalError::sqlite("cannot get non-key columns", e)),
             }
         }
     }
 
-    /// Get the value from a non-key column.
-    pub fn get_nonkey(&'a self, id: i64, column_name: &str) -> Result<String, NormalError> {
+    /// Get the value from a non-key column, e.g. as `String`, `i64`, `f64`,
+    /// `bool` or `Vec<u8>`.  Read `Option<T>` to accept NULL.
+    pub fn get_nonkey<T: FromSql>(&self, id: i64, column_name: &str) -> Result<T, NormalError> {
         let query = format!(
-            "SELECT {} FROM {} WHERE rowid={};",
-            column_name, self.table_name, id
-        );
-        let statement_opt = self.conn.prepare(query);
-        if statement_opt.is_err() {
-            // let err = statement_opt.unwrap_err();
-            return Err(NormalError {
-                msg: format!("missing non-key column {}", column_name),
-            });
-        }
+            "SELECT {}.{} FROM {} WHERE rowid = ?",
+            self.table_name.quoted(),
+            Identifier::new(column_name)?.quoted(),
+            self.table_name.quoted()
+        ); // Qualify the column, so that a missing one is an error.
+        let value = self
+            .conn
+            .reader()
+            .with_statement(&query, |statement| {
+                statement.bind(1, id)?;
+                match statement.next() {
+                    Ok(State::Row) => Ok(statement.read::<Value>(0)?),
+                    Ok(State::Done) => Err(NormalError::NotFound(format!(
+                        "cannot read non-key column {}: invalid id {}",
+                        column_name, id
+                    ))),
+                    Err(e) => Err(NormalError::sqlite(
+                        format!("cannot read non-key column {}", column_name),
+                        e,
+                    )),
+                }
+            })
+            .map_err(|e| missing_column(column_name, e))?;
+        read_nonkey(value, column_name, id)
+    }
 
-        let mut statement = statement_opt.unwrap();
-        match statement.next() {
-            Ok(State::Row) => match statement.read::<String>(0) {
-                Ok(value) => Ok(value),
-                Err(e) => Err(NormalError {
-                    msg: format!(
-                        "uninitialized non-key column {} for id {}: {}",
-                        column_name,
-                        id,
-                        unwrap_msg!(e)
-                    ),
-                }),
-            },
-            Ok(State::Done) => Err(NormalError {
-                msg: format!(
-                    "cannot read non-key column {}: invalid id {}",
-                    column_name, id
-                ),
-            }),
-            Err(e) => Err(NormalError {
-                msg: format!(
-                    "cannot read non-key column {}: {}",
-                    column_name,
-                    unwrap_msg!(e)
-                ),
-            }),
-        }
+    /// Get the key and every non-key value of a row in one query.
+    pub fn get_record(&self, id: i64) -> Result<Record, NormalError> {
+        let query = format!("SELECT * FROM {} WHERE rowid = ?", self.table_name.quoted());
+        self.conn.reader().with_statement(&query, |statement| {
+            statement.bind(1, id)?;
+            match statement.next() {
+                Ok(State::Row) => {
+                    let mut key = None;
+                    let mut nonkeys = BTreeMap::new();
+                    for i in 0..statement.count() {
+                        let name = statement.name(i).to_string();
+                        if name == self.column_name.as_str() {
+                            key = Some(statement.read::<String>(i)?);
+                        } else if name != ID_COLUMN {
+                            nonkeys.insert(name, statement.read::<Value>(i)?);
+                        }
+                    }
+                    let key = key.ok_or_else(|| {
+                        NormalError::SchemaMismatch(format!(
+                            "table {} has no column {}",
+                            self.table_name, self.column_name
+                        ))
+                    })?;
+                    Ok(Record { id, key, nonkeys })
+                }
+                Ok(State::Done) => Err(NormalError::NotFound(format!("invalid id {}", id))),
+                Err(e) => Err(NormalError::sqlite(format!("cannot read record {}", id), e)),
+            }
+        })
     }
 
-    /// Associate a non-key value with a row.
-    pub fn notate(&'a self, id: i64, column_name: &str, note: &str) -> Result<(), NormalError> {
+    /// Associate a non-key value with a row.  Values of another type than
+    /// the column's fail with a constraint error.
+    pub fn notate<T: ToSql>(&self, id: i64, column_name: &str, note: T) -> Result<(), NormalError> {
         let query = format!(
-            "UPDATE {} SET {}=? WHERE rowid={};",
-            self.table_name, column_name, id
+            "UPDATE {} SET {} = ? WHERE rowid = ?;",
+            self.table_name.quoted(),
+            Identifier::new(column_name)?.quoted()
         );
-        let mut statement = self.conn.prepare(query).unwrap();
-        statement.bind(1, note).unwrap();
-        match statement.next() {
-            Ok(_) => Ok(()),
-            Err(e) => Err(NormalError {
-                msg: format!("cannot notate column {}: {}", column_name, unwrap_msg!(e)),
-            }),
+        self.conn
+            .writer()
+            .with_statement(&query, |statement| {
+                statement.bind(1, &note.to_sql())?;
+                statement.bind(2, id)?;
+                match statement.next() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(NormalError::sqlite(
+                        format!("cannot notate column {}", column_name),
+                        e,
+                    )),
+                }
+            })
+            .map_err(|e| missing_column(column_name, e))
+    }
+
+    /// Associate several non-key values with a row in one statement.  Use
+    /// `Value` to mix types, e.g. `("count", 3.to_sql())`.
+    pub fn notate_many<C: AsRef<str>, T: ToSql>(
+        &self,
+        id: i64,
+        notes: &[(C, T)],
+    ) -> Result<(), NormalError> {
+        if notes.is_empty() {
+            return Ok(());
         }
+        let assignments = notes
+            .iter()
+            .map(|(column_name, _)| {
+                Ok(format!(
+                    "{} = ?",
+                    Identifier::new(column_name.as_ref())?.quoted()
+                ))
+            })
+            .collect::<Result<Vec<String>, NormalError>>()?;
+        let query = format!(
+            "UPDATE {} SET {} WHERE rowid = ?;",
+            self.table_name.quoted(),
+            assignments.join(", ")
+        );
+        self.conn
+            .writer()
+            .with_statement(&query, |statement| {
+                for (i, (_, note)) in notes.iter().enumerate() {
+                    statement.bind(i + 1, &note.to_sql())?;
+                }
+                statement.bind(notes.len() + 1, id)?;
+                match statement.next() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(NormalError::sqlite(format!("cannot notate id {}", id), e)),
+                }
+            })
+            .map_err(|e| {
+                // Name the column SQLite reports missing.
+                let missing = notes
+                    .iter()
+                    .map(|(column_name, _)| column_name.as_ref())
+                    .find(|column_name| {
+                        e.to_string()
+                            .ends_with(&format!("no such column: {}", column_name))
+                    });
+                match missing {
+                    Some(column_name) => missing_column(column_name, e),
+                    None => e,
+                }
+            })
+    }
+
+    /// Run a query with `BULK_BATCH` parameters over the keys in batches,
+    /// reading each resulting row.
+    fn query_batched<K>(
+        &self,
+        query: &str,
+        keys: &[K],
+        bind: impl Fn(&mut Statement, usize, &K) -> sqlite::Result<()>,
+        mut read: impl FnMut(&Statement) -> Result<(), NormalError>,
+    ) -> Result<(), NormalError> {
+        let conn = self.conn.reader();
+        for batch in keys.chunks(BULK_BATCH) {
+            conn.with_statement(query, |statement| {
+                // Pad short batches by repeating a key, so that every batch
+                // reuses the same statement.
+                for i in 0..BULK_BATCH {
+                    bind(statement, i + 1, &batch[i.min(batch.len() - 1)])?;
+                }
+                while statement
+                    .next()
+                    .map_err(|e| NormalError::sqlite("failed to read batch", e))?
+                    == State::Row
+                {
+                    read(statement)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
     }
 
     /// Private function to return an iterator to a search result.
     fn search_min(
-        &'a self,
+        &self,
         value: &str,
+        mode: SearchMode,
         min_idx: i64,
         max_res: i64,
-    ) -> Result<impl 'a + Iterator<Item = (i64, String)>, NormalError> {
+        with_aliases: bool,
+    ) -> Result<impl '_ + Iterator<Item = Result<(i64, String), NormalError>>, NormalError> {
+        let column = self.column_name.quoted();
+        let matches = if with_aliases {
+            format!(
+                "({} OR rowid IN (SELECT id FROM {} WHERE {}))",
+                mode.condition(&column),
+                self.alias_table.quoted(),
+                mode.condition("alias")
+            )
+        } else {
+            mode.condition(&column)
+        };
         let query = format!(
-            "SELECT rowid, {} FROM {} WHERE {} LIKE ? AND rowid > ? ORDER BY rowid LIMIT ?",
-            self.column_name, self.table_name, self.column_name
+            "SELECT rowid, {} FROM {} WHERE {} AND rowid > ?2 ORDER BY rowid LIMIT ?3",
+            column,
+            self.table_name.quoted(),
+            matches
+        );
+        // Patterns follow the policy, except regular expressions and
+        // escaped patterns, whose syntax it could change, e.g. by folding
+        // the escape character.
+        let value = match mode {
+            SearchMode::Regex | SearchMode::LikeEscape(_) => value.to_string(),
+            _ => self.policy.apply(value).into_owned(),
+        };
+        let (pattern, extra) = match mode {
+            // Blobs sort after all text, so bound unbounded prefixes by one.
+            SearchMode::Prefix => match prefix_upper_bound(&value) {
+                Some(bound) => (value, Some(Value::String(bound))),
+                None => (value, Some(Value::Binary(vec![]))),
+            },
+            SearchMode::Contains => (
+                format!("%{}%", escape_like(&value, '\\')),
+                Some(Value::String("\\".to_string())),
+            ),
+            SearchMode::LikeEscape(escape) => (value, Some(Value::String(escape.to_string()))),
+            _ => (value, None),
+        };
+        let mut params = vec![
+            Value::String(pattern),
+            Value::Integer(min_idx),
+            Value::Integer(max_res),
+        ];
+        params.extend(extra);
+        new_search_string_iterator(self.conn.reader(), &query, &params)
+    }
+
+    /// Index the key column and the given non-key columns for `search_fts`.
+    /// The index is kept in sync by triggers, and persists in the database;
+    /// enabling it again with the same columns does nothing.
+    pub fn enable_fts<T: AsRef<str>>(
+        &self,
+        nonkeys: impl IntoIterator<Item = T>,
+    ) -> Result<(), NormalError> {
+        let mut columns = vec![self.column_name.clone()];
+        for nonkey in nonkeys {
+            columns.push(Identifier::new(nonkey.as_ref().to_string())?);
+        }
+        let tx = self.transaction()?;
+        let conn = self.conn.writer();
+        let existing = table_columns(conn.conn(), &self.fts_table)?;
+        if existing.is_empty() {
+            create_fts_table(conn.conn(), &self.table_name, &self.fts_table, &columns, "")?;
+        } else if existing
+            .iter()
+            .map(String::as_str)
+            .ne(columns.iter().map(Identifier::as_str))
+        {
+            return Err(NormalError::SchemaMismatch(format!(
+                "full-text index {} already covers columns {}",
+                self.fts_table,
+                existing.join(", ")
+            )));
+        }
+        drop(conn);
+        tx.commit()
+    }
+
+    /// Return the ids and tokens matching a full-text query, best first by
+    /// bm25 rank.  Queries use FTS5 syntax, e.g. `jazz`, `jaz*` or
+    /// `"bossa nova"`, and match case and accents insensitively.
+    /// Requires `enable_fts`.  Read failures, including query syntax
+    /// errors, are yielded as errors and end the iteration.
+    pub fn search_fts(
+        &self,
+        query: &str,
+    ) -> Result<impl '_ + Iterator<Item = Result<(i64, String), NormalError>>, NormalError> {
+        let sql = format!(
+            "
+                SELECT {fts}.rowid, {table}.{column}
+                FROM {fts} JOIN {table} ON {table}.rowid = {fts}.rowid
+                WHERE {fts} MATCH ? ORDER BY bm25({fts})
+                ",
+            fts = self.fts_table.quoted(),
+            table = self.table_name.quoted(),
+            column = self.column_name.quoted()
+        );
+        new_search_string_iterator(
+            self.conn.reader(),
+            &sql,
+            &[Value::String(query.to_string())],
+        )
+        .map_err(|e| missing_index("full-text", &self.table_name, e))
+    }
+
+    /// Index the keys by trigrams for `lookup_fuzzy`.  The index is kept in
+    /// sync by triggers, and persists in the database.
+    pub fn enable_fuzzy(&self) -> Result<(), NormalError> {
+        let tx = self.transaction()?;
+        let conn = self.conn.writer();
+        if table_columns(conn.conn(), &self.trigram_table)?.is_empty() {
+            create_fts_table(
+                conn.conn(),
+                &self.table_name,
+                &self.trigram_table,
+                std::slice::from_ref(&self.column_name),
+                ", tokenize='trigram'",
+            )?;
+        }
+        drop(conn);
+        tx.commit()
+    }
+
+    /// Return up to `limit` keys spelled similarly to the value, as
+    /// `(id, key, score)` with scores from `threshold` to 1, best first.
+    /// Scores compare trigrams of the words, so "blugrass" finds
+    /// "bluegrass".  Values shorter than three characters match nothing.
+    /// Requires `enable_fuzzy`.
+    pub fn lookup_fuzzy(
+        &self,
+        value: &str,
+        threshold: f64,
+        limit: usize,
+    ) -> Result<Vec<(i64, String, f64)>, NormalError> {
+        let value = self.policy.apply(value);
+        let query = match trigram_query(&value) {
+            Some(query) => query,
+            None => return Ok(vec![]),
+        };
+        // Score the candidates sharing the most trigrams.
+        let sql = format!(
+            "
+                SELECT {trigrams}.rowid, {table}.{column}
+                FROM {trigrams} JOIN {table} ON {table}.rowid = {trigrams}.rowid
+                WHERE {trigrams} MATCH ? ORDER BY bm25({trigrams}) LIMIT ?
+                ",
+            trigrams = self.trigram_table.quoted(),
+            table = self.table_name.quoted(),
+            column = self.column_name.quoted()
         );
-        let mut cursor = self.conn.prepare(query).unwrap().cursor();
-        cursor
-            .bind(&[
-                Value::String(value.to_string()),
-                Value::Integer(min_idx),
-                Value::Integer(max_res),
-            ])
-            .unwrap();
-        Ok(new_search_string_iterator(cursor))
+        let candidates = i64::try_from(limit.saturating_mul(FUZZY_CANDIDATES)).unwrap_or(i64::MAX);
+        let mut matches = vec![];
+        for candidate in new_search_string_iterator(
+            self.conn.reader(),
+            &sql,
+            &[Value::String(query), Value::Integer(candidates)],
+        )
+        .map_err(|e| missing_index("trigram", &self.table_name, e))?
+        {
+            let (id, key) = candidate?;
+            let score = trigram_similarity(&value, &key);
+            if score >= threshold {
+                matches.push((id, key, score));
+            }
+        }
+        matches.sort_by(|a, b| b.2.total_cmp(&a.2).then(a.0.cmp(&b.0)));
+        matches.truncate(limit);
+        Ok(matches)
     }
 
     /// Return the ids of tokens matching the search string.
-    /// Use of SQL '%' wildcards is acceptable, and enables substring search.
+    /// Use of SQL '%' wildcards is acceptable, and enables substring search;
+    /// `SearchMode::Contains` matches them literally.
+    /// Read failures are yielded as errors and end the iteration.
     pub fn search(
-        &'a self,
+        &self,
+        value: &str,
+    ) -> Result<impl '_ + Iterator<Item = Result<(i64, String), NormalError>>, NormalError> {
+        self.search_min(value, SearchMode::Like, i64::MIN, i64::MAX, false)
+    }
+
+    /// Return the ids of tokens matching the search string in a mode, e.g.
+    /// `SearchMode::Regex`.
+    /// Read failures, including invalid patterns, are yielded as errors and
+    /// end the iteration.
+    pub fn search_with_mode(
+        &self,
         value: &str,
-    ) -> Result<impl 'a + Iterator<Item = (i64, String)>, NormalError> {
-        self.search_min(value, i64::MIN, i64::MAX)
+        mode: SearchMode,
+    ) -> Result<impl '_ + Iterator<Item = Result<(i64, String), NormalError>>, NormalError> {
+        self.search_min(value, mode, i64::MIN, i64::MAX, false)
+    }
+
+    /// Return the ids of tokens matching the search string either directly
+    /// or through one of their aliases.
+    /// Read failures are yielded as errors and end the iteration.
+    pub fn search_with_aliases(
+        &self,
+        value: &str,
+    ) -> Result<impl '_ + Iterator<Item = Result<(i64, String), NormalError>>, NormalError> {
+        self.search_min(value, SearchMode::Like, i64::MIN, i64::MAX, true)
     }
 
     /// Return the ids of tokens matching the search string limited by the
     /// size of the destination vector and last index seen.
     /// Use of SQL '%' wildcards is acceptable, and enables substring search.
     pub fn search_page(
-        &'a self,
+        &self,
         value: &str,
         last_idx: i64,
-        dest: &mut Vec<(i64, String)>,
+        dest: &mut [(i64, String)],
     ) -> Result<usize, NormalError> {
-        match self.search_min(value, last_idx, dest.len().try_into().unwrap()) {
-            Ok(mut it) => {
-                let mut i = 0;
-                let sz = dest.len();
-                while i < sz {
-                    match it.next() {
-                        Some(pair) => {
-                            dest[i] = pair;
-                            i += 1;
-                        }
-                        _ => return Ok(i),
-                    }
+        self.search_page_with_mode(value, SearchMode::Like, last_idx, dest)
+    }
+
+    /// Return the ids of tokens matching the search string in a mode,
+    /// limited by the size of the destination vector and last index seen.
+    pub fn search_page_with_mode(
+        &self,
+        value: &str,
+        mode: SearchMode,
+        last_idx: i64,
+        dest: &mut [(i64, String)],
+    ) -> Result<usize, NormalError> {
+        let limit = i64::try_from(dest.len()).unwrap_or(i64::MAX);
+        let mut it = self.search_min(value, mode, last_idx, limit, false)?;
+        let mut i = 0;
+        let sz = dest.len();
+        while i < sz {
+            match it.next() {
+                Some(pair) => {
+                    dest[i] = pair?;
+                    i += 1;
                 }
-                return Ok(i);
+                _ => return Ok(i),
             }
-            Err(e) => return Err(e),
         }
+        Ok(i)
+    }
+
+    /// Return a page of up to `limit` tokens matching the search string in
+    /// a mode, starting after the cursor, or from the first match without
+    /// one.  Pages hold at least one item.
+    pub fn search_paged(
+        &self,
+        value: &str,
+        mode: SearchMode,
+        cursor: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<(i64, String)>, NormalError> {
+        let after = match cursor {
+            Some(cursor) => cursor.position(CursorKind::Search)?.0,
+            None => i64::MIN,
+        };
+        let limit = limit.max(1);
+        let fetch = i64::try_from(limit.saturating_add(1)).unwrap_or(i64::MAX);
+        let items = self
+            .search_min(value, mode, after, fetch, false)?
+            .collect::<Result<Vec<(i64, String)>, NormalError>>()?;
+        Ok(Page::from_items(items, limit, |(id, _)| {
+            Cursor::new(CursorKind::Search, *id, 0)
+        }))
     }
 }
 
-/// Create a DB connection with tables and indices, if they do not already exist.
-fn open(path: &str, table_name: &str, column_name: &str) -> Result<Connection, sqlite::Error> {
-    let conn = sqlite::open(path).unwrap();
-    let index_name = format!("idx_{}_{}", table_name, column_name);
+/// The column holding ids, absent from tables created by early versions.
+const ID_COLUMN: &str = "rowid";
+
+/// Number of keys bound in each `get_bulk` or `lookup_bulk` query.
+const BULK_BATCH: usize = 256;
+
+/// Number of index candidates scored by `lookup_fuzzy` per result.
+const FUZZY_CANDIDATES: usize = 20;
+
+/// Whether the SQLite library supports `INSERT ... RETURNING`, added in 3.35.
+fn supports_returning() -> bool {
+    sqlite::version() >= 3_035_000
+}
+
+/// Classify a failure to prepare a statement naming a non-key column.
+fn missing_column(column_name: &str, e: NormalError) -> NormalError {
+    match &e {
+        NormalError::Sqlite { source, .. }
+            if source
+                .message
+                .as_deref()
+                .is_some_and(|msg| msg.contains("no such column")) =>
+        {
+            NormalError::SchemaMismatch(format!("missing non-key column {}", column_name))
+        }
+        _ => e,
+    }
+}
+
+/// Create the table and indices, if they do not already exist.  An existing
+/// table must have the key column; returns its name as the table spells it.
+fn create_table(
+    conn: &Connection,
+    table_name: &Identifier,
+    column_name: &Identifier,
+) -> Result<Identifier<'static>, NormalError> {
+    // Name the id column after the rowid it aliases, so that queries work on
+    // tables created before it was declared.  AUTOINCREMENT keeps the ids of
+    // merged and deleted values from being handed out again.
+    let query = format!(
+        "CREATE TABLE IF NOT EXISTS {} ({} INTEGER PRIMARY KEY AUTOINCREMENT, {} TEXT UNIQUE);",
+        table_name.quoted(),
+        ID_COLUMN,
+        column_name.quoted()
+    );
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create table {}", table_name), e))?;
+    let column_name = check_columns(conn, table_name, &[column_name])?.remove(0);
+    let query = format!(
+        "CREATE INDEX IF NOT EXISTS {} ON {} ({});",
+        Identifier::index_name(table_name, &column_name),
+        table_name.quoted(),
+        column_name.quoted()
+    );
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create table {}", table_name), e))?;
+    Ok(column_name)
+}
+
+/// Create the table of aliases and its index, if they do not already exist.
+fn create_alias_table(conn: &Connection, alias_table: &Identifier) -> Result<(), NormalError> {
+    let id_column = Identifier::new("id")?;
+    let query = format!(
+        "
+            CREATE TABLE IF NOT EXISTS {} (alias TEXT UNIQUE, id INTEGER);
+            CREATE INDEX IF NOT EXISTS {} ON {} (id);
+            ",
+        alias_table.quoted(),
+        Identifier::index_name(alias_table, &id_column),
+        alias_table.quoted()
+    );
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create table {}", alias_table), e))
+}
+
+/// The least string above every string starting with the prefix, or `None`
+/// if there is none, e.g. for the empty prefix.
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        // Skip the surrogate range, which holds no chars.
+        let next = match last {
+            '\u{d7ff}' => Some('\u{e000}'),
+            _ => char::from_u32(u32::from(last) + 1),
+        };
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// Classify a failure to query a missing index table.
+fn missing_index(kind: &str, table_name: &Identifier, e: NormalError) -> NormalError {
+    match &e {
+        NormalError::Sqlite { source, .. }
+            if source
+                .message
+                .as_deref()
+                .is_some_and(|msg| msg.contains("no such table")) =>
+        {
+            NormalError::SchemaMismatch(format!("missing {} index for table {}", kind, table_name))
+        }
+        _ => e,
+    }
+}
+
+/// Create a full-text index over columns of the table, with triggers keeping
+/// it in sync, and index the existing rows.  Options such as a tokenizer
+/// follow the content options.
+fn create_fts_table(
+    conn: &Connection,
+    table_name: &Identifier,
+    fts_table: &Identifier,
+    columns: &[Identifier],
+    options: &str,
+) -> Result<(), NormalError> {
+    let names = columns
+        .iter()
+        .map(Identifier::quoted)
+        .collect::<Vec<String>>()
+        .join(", ");
+    let values = |row: &str| {
+        columns
+            .iter()
+            .map(|column| format!("{}.{}", row, column.quoted()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let trigger = |suffix: &str| Identifier::new(format!("{}_{}", fts_table, suffix));
     let query = format!(
         "
-            CREATE TABLE IF NOT EXISTS {} ({} TEXT UNIQUE);
-            CREATE INDEX IF NOT EXISTS {} ON {} ({});
+            CREATE VIRTUAL TABLE {fts} USING fts5(
+                {names}, content={table}, content_rowid=rowid{options}
+            );
+            CREATE TRIGGER {insert} AFTER INSERT ON {table} BEGIN
+                INSERT INTO {fts} (rowid, {names}) VALUES (new.rowid, {new});
+            END;
+            CREATE TRIGGER {delete} AFTER DELETE ON {table} BEGIN
+                INSERT INTO {fts} ({fts}, rowid, {names}) VALUES ('delete', old.rowid, {old});
+            END;
+            CREATE TRIGGER {update} AFTER UPDATE ON {table} BEGIN
+                INSERT INTO {fts} ({fts}, rowid, {names}) VALUES ('delete', old.rowid, {old});
+                INSERT INTO {fts} (rowid, {names}) VALUES (new.rowid, {new});
+            END;
+            INSERT INTO {fts} ({fts}) VALUES ('rebuild');
             ",
-        table_name,
-        column_name,
-        index_name.as_str(),
-        table_name,
-        column_name
+        fts = fts_table.quoted(),
+        table = table_name.quoted(),
+        names = names,
+        options = options,
+        new = values("new"),
+        old = values("old"),
+        insert = trigger("insert")?.quoted(),
+        delete = trigger("delete")?.quoted(),
+        update = trigger("update")?.quoted(),
     );
-    {
-        let mut statement = conn.prepare(query)?;
-        statement.next()?;
-    } // Returning existing connection returns a borrowed value, running statement.drop()
-      // outside lifetime, unless we're explicit about statement lifetime.
-    Ok(conn)
+    conn.execute(query)
+        .map_err(|e| NormalError::sqlite(format!("cannot create full-text index {}", fts_table), e))
 }
 
-/// Create a DB connection with non-key columns, adding them if necessary.
-fn open_with_nonkeys<'a, T: AsRef<str>>(
-    path: &str,
-    table_name: &str,
-    column_name: &str,
-    nonkeys: impl Iterator<Item = T>,
-) -> Result<Connection, sqlite::Error> {
-    let conn = open(path, table_name, column_name)?;
+/// Add non-key columns to the table, if they do not already exist.
+fn add_nonkeys(
+    conn: &Connection,
+    table_name: &Identifier,
+    nonkeys: impl Iterator<Item = NonKeyColumn>,
+) -> Result<(), NormalError> {
     for nonkey in nonkeys {
+        let column_name = Identifier::new(nonkey.name.as_str())?.quoted();
         let query = format!(
-            "ALTER TABLE {} ADD COLUMN {} TEXT;",
-            table_name,
-            nonkey.as_ref()
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table_name.quoted(),
+            column_name,
+            nonkey.definition(&column_name)
         );
         match conn.prepare(query) {
             Ok(mut statement) => {
                 statement.next()?;
             }
             Err(alter_err) => {
-                let msg = unwrap_msg!(alter_err);
-                if !msg.contains("duplicate column name") {
-                    return Err(sqlite::Error {
-                        code: alter_err.code,
-                        message: Some(format!(
-                            "cannot add nonkey column {}: {}",
-                            nonkey.as_ref(),
-                            msg
-                        )),
-                    });
+                let duplicate = alter_err
+                    .message
+                    .as_deref()
+                    .is_some_and(|msg| msg.contains("duplicate column name"));
+                if !duplicate {
+                    return Err(NormalError::sqlite(
+                        format!("cannot add nonkey column {}", nonkey.name),
+                        alter_err,
+                    ));
                 }
             }
         }
     }
-    Ok(conn)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1,7 +1,7 @@
 use super::*;
 use tempfile::tempdir;
 
-fn new_table<'a>() -> Result<Normal<'a>, sqlite::Error> {
+fn new_table() -> Result<Normal, NormalError> {
     Normal::new(":memory:", "names", "name")
 }
 
@@ -24,7 +24,9 @@ fn it_inserts_values() {
 #[test]
 fn handles_missing_key_get() {
     let norm = new_table().unwrap();
-    assert_eq!(norm.get(87).unwrap_err().msg, "missing key: 87");
+    let err = norm.get(87).unwrap_err();
+    assert!(matches!(err, NormalError::NotFound(_)));
+    assert_eq!(err.to_string(), "missing key: 87");
 }
 
 /// Ensure that we can search by substring.
@@ -33,20 +35,29 @@ fn it_searches() {
     let value = "bluegrass";
     let norm = new_table().unwrap();
 
-    assert_eq!(norm.search("%").unwrap().next(), None);
+    assert!(norm.search("%").unwrap().next().is_none());
     let id = norm.create(value).unwrap();
     assert_eq!(norm.get(id).unwrap(), value);
 
     assert_eq!(
-        norm.search("%").unwrap().collect::<Vec<(i64, String)>>(),
+        norm.search("%")
+            .unwrap()
+            .collect::<Result<Vec<(i64, String)>, NormalError>>()
+            .unwrap(),
         vec![(id, value.to_string())]
     );
     assert_eq!(
-        norm.search(value).unwrap().collect::<Vec<(i64, String)>>(),
+        norm.search(value)
+            .unwrap()
+            .collect::<Result<Vec<(i64, String)>, NormalError>>()
+            .unwrap(),
         vec![(id, value.to_string())]
     );
     assert_eq!(
-        norm.search("").unwrap().collect::<Vec<(i64, String)>>(),
+        norm.search("")
+            .unwrap()
+            .collect::<Result<Vec<(i64, String)>, NormalError>>()
+            .unwrap(),
         Vec::<(i64, String)>::new()
     );
 }
@@ -71,11 +82,32 @@ fn open_cursors_are_updated() {
     assert_eq!(norm.get(2).unwrap(), "jazz");
 }
 
-/// Demonstrate panic from SQLite keyword use -- should we fix this.
+/// SQLite keywords are quoted and may be used as names.
 #[test]
-#[should_panic(expected = "near \\\"values\\\": syntax error")]
-fn demo_invalid_table_setup() {
-    Normal::new(":memory:", "values", "value").unwrap();
+fn it_accepts_keyword_table_names() {
+    let norm = Normal::new(":memory:", "values", "value").unwrap();
+    let id = norm.create("bluegrass").unwrap();
+    assert_eq!(norm.get(id).unwrap(), "bluegrass");
+}
+
+/// Invalid names are reported as errors.
+#[test]
+fn it_rejects_invalid_table_names() {
+    assert_eq!(
+        Normal::new(":memory:", "", "value")
+            .err()
+            .unwrap()
+            .to_string(),
+        "invalid identifier \"\": empty name"
+    );
+}
+
+/// Hostile names cannot inject SQL.
+#[test]
+fn it_quotes_hostile_names() {
+    let norm = Normal::new(":memory:", "names", "name\" TEXT); DROP TABLE names; --").unwrap();
+    norm.create("bluegrass").unwrap();
+    assert_eq!(norm.search("%").unwrap().count(), 1);
 }
 
 /// Demonstrate intent of case-sensitive values.
@@ -121,9 +153,11 @@ fn updates_non_key() {
     let nonkeys = ["address", "mantra"];
     let norm = Normal::new_with_nonkeys(":memory:", "names", "name", nonkeys.iter()).unwrap();
     let id = norm.create("bilbo").unwrap();
-    norm.notate(id, nonkeys.get(0).unwrap(), "Bag End").unwrap();
+    norm.notate(id, nonkeys.first().unwrap(), "Bag End")
+        .unwrap();
     assert_eq!(
-        norm.get_nonkey(id, nonkeys.get(0).unwrap()).unwrap(),
+        norm.get_nonkey::<String>(id, nonkeys.first().unwrap())
+            .unwrap(),
         "Bag End"
     );
 }
@@ -134,11 +168,13 @@ fn error_on_missing_non_key_value() {
     let nonkeys = ["address", "mantra"];
     let norm = Normal::new_with_nonkeys(":memory:", "names", "name", nonkeys.iter()).unwrap();
     let id = norm.create("bilbo").unwrap();
+    let err = norm
+        .get_nonkey::<String>(id, nonkeys.get(1).unwrap())
+        .unwrap_err();
+    assert!(matches!(err, NormalError::NotFound(_)));
     assert_eq!(
-        norm.get_nonkey(id, nonkeys.get(1).unwrap())
-            .unwrap_err()
-            .msg,
-        "uninitialized non-key column mantra for id 1: cannot read a text column"
+        err.to_string(),
+        "uninitialized non-key column mantra for id 1"
     );
 }
 
@@ -147,12 +183,474 @@ fn error_on_missing_non_key_value() {
 fn error_on_missing_column() {
     let norm = new_table().unwrap();
     let id = norm.create("bilbo").unwrap();
+    let err = norm.get_nonkey::<String>(id, "superpower").unwrap_err();
+    assert!(matches!(err, NormalError::SchemaMismatch(_)));
+    assert_eq!(err.to_string(), "missing non-key column superpower");
+}
+
+/// Reopening a table with a misspelled column fails without touching it.
+#[test]
+fn error_on_misspelled_column() {
+    let db = Database::open(":memory:").unwrap();
+    db.normal("names", "name").unwrap();
+    let err = db.normal("names", "nmae").err().unwrap();
+    assert!(matches!(err, NormalError::SchemaMismatch(_)));
+    assert_eq!(err.to_string(), "table names has no column nmae");
+    let norm = db.normal("names", "NAME").unwrap();
+    let count = norm
+        .conn
+        .reader()
+        .with_statement(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_names_nmae'",
+            |statement| {
+                statement.next()?;
+                Ok(statement.read::<i64>(0)?)
+            },
+        )
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+/// Columns named in another case are read as the table spells them.
+#[test]
+fn it_opens_columns_in_another_case() {
+    let db = Database::open(":memory:").unwrap();
+    let norm = db
+        .normal_with_nonkeys("names", "name", ["note"].iter())
+        .unwrap();
+    let id = norm.create("bilbo").unwrap();
+    let upper = db.normal("names", "NAME").unwrap();
+    let record = upper.get_record(id).unwrap();
+    assert_eq!(record.key, "bilbo");
+    assert_eq!(record.nonkeys.keys().collect::<Vec<_>>(), ["note"]);
+    assert_eq!(upper.get_nonkeys().unwrap(), vec!["note"]);
+}
+
+/// Typed non-key columns round-trip their values.
+#[test]
+fn it_notates_typed_columns() {
+    let norm = Normal::new_with_typed_nonkeys(
+        ":memory:",
+        "names",
+        "name",
+        vec![
+            NonKeyColumn::new("count", ColumnType::Integer),
+            NonKeyColumn::new("weight", ColumnType::Real),
+            NonKeyColumn::new("hidden", ColumnType::Integer),
+            NonKeyColumn::new("digest", ColumnType::Blob),
+        ],
+    )
+    .unwrap();
+    let id = norm.create("bilbo").unwrap();
+    norm.notate(id, "count", 111i64).unwrap();
+    norm.notate(id, "weight", 0.5).unwrap();
+    norm.notate(id, "hidden", true).unwrap();
+    norm.notate(id, "digest", &[1u8, 2, 3][..]).unwrap();
+
+    assert_eq!(norm.get_nonkey::<i64>(id, "count").unwrap(), 111);
+    assert_eq!(norm.get_nonkey::<f64>(id, "weight").unwrap(), 0.5);
+    assert!(norm.get_nonkey::<bool>(id, "hidden").unwrap());
+    assert_eq!(
+        norm.get_nonkey::<Vec<u8>>(id, "digest").unwrap(),
+        vec![1, 2, 3]
+    );
+
+    norm.notate(id, "count", None::<i64>).unwrap();
+    assert_eq!(norm.get_nonkey::<Option<i64>>(id, "count").unwrap(), None);
+    assert!(matches!(
+        norm.get_nonkey::<i64>(id, "count"),
+        Err(NormalError::NotFound(_))
+    ));
+}
+
+/// Values of another type are rejected on write and on read.
+#[test]
+fn error_on_type_mismatch() {
+    let norm = Normal::new_with_typed_nonkeys(
+        ":memory:",
+        "names",
+        "name",
+        vec![NonKeyColumn::new("count", ColumnType::Integer)],
+    )
+    .unwrap();
+    let id = norm.create("bilbo").unwrap();
+    assert!(matches!(
+        norm.notate(id, "count", "many"),
+        Err(NormalError::Constraint { .. })
+    ));
+    // Integer affinity converts numeric text.
+    norm.notate(id, "count", "42").unwrap();
+
+    let err = norm.get_nonkey::<String>(id, "count").unwrap_err();
+    assert!(matches!(
+        err,
+        NormalError::TypeMismatch {
+            expected: ColumnType::Text,
+            found: ColumnType::Integer,
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "non-key column count holds INTEGER, not TEXT"
+    );
+}
+
+/// Columns that are not nullable start out as zero.
+#[test]
+fn it_defaults_not_null_columns() {
+    let norm = Normal::new_with_typed_nonkeys(
+        ":memory:",
+        "names",
+        "name",
+        vec![NonKeyColumn {
+            nullable: false,
+            ..NonKeyColumn::new("count", ColumnType::Integer)
+        }],
+    )
+    .unwrap();
+    let id = norm.create("bilbo").unwrap();
+    assert_eq!(norm.get_nonkey::<i64>(id, "count").unwrap(), 0);
+    assert!(matches!(
+        norm.notate(id, "count", None::<i64>),
+        Err(NormalError::Constraint { .. })
+    ));
+}
+
+/// A record holds the key and every non-key value of a row.
+#[test]
+fn it_gets_records() {
+    let norm = Normal::new_with_typed_nonkeys(
+        ":memory:",
+        "names",
+        "name",
+        vec![
+            NonKeyColumn::new("address", ColumnType::Text),
+            NonKeyColumn::new("rings", ColumnType::Integer),
+        ],
+    )
+    .unwrap();
+    let id = norm.create("bilbo").unwrap();
+    norm.notate_many(
+        id,
+        &[("address", "Bag End".to_sql()), ("rings", 1.to_sql())],
+    )
+    .unwrap();
+
+    let record = norm.get_record(id).unwrap();
+    assert_eq!(record.id, id);
+    assert_eq!(record.key, "bilbo");
+    assert_eq!(record.nonkeys.len(), 2);
+    assert_eq!(record.get::<String>("address").unwrap(), "Bag End");
+    assert_eq!(record.get::<i64>("rings").unwrap(), 1);
+    assert!(matches!(
+        record.get::<String>("rings"),
+        Err(NormalError::TypeMismatch { .. })
+    ));
+    assert!(matches!(
+        record.get::<String>("mantra"),
+        Err(NormalError::SchemaMismatch(_))
+    ));
+    assert!(matches!(
+        norm.get_record(id + 1),
+        Err(NormalError::NotFound(_))
+    ));
+}
+
+/// Notating several columns names a missing one.
+#[test]
+fn it_notates_many() {
+    let nonkeys = ["address", "mantra"];
+    let norm = Normal::new_with_nonkeys(":memory:", "names", "name", nonkeys.iter()).unwrap();
+    let id = norm.create("bilbo").unwrap();
+    norm.notate_many(id, &[("address", "Bag End"), ("mantra", "adventure")])
+        .unwrap();
+    assert_eq!(
+        norm.get_nonkey::<String>(id, "mantra").unwrap(),
+        "adventure"
+    );
+    norm.notate_many::<&str, &str>(id, &[]).unwrap();
+
+    let err = norm
+        .notate_many(
+            id,
+            &[("address", "Rivendell"), ("superpower", "invisibility")],
+        )
+        .unwrap_err();
+    assert!(matches!(err, NormalError::SchemaMismatch(_)));
+    assert_eq!(err.to_string(), "missing non-key column superpower");
+    assert_eq!(norm.get_nonkey::<String>(id, "address").unwrap(), "Bag End");
+}
+
+fn fts_ids(norm: &Normal, query: &str) -> Vec<i64> {
+    norm.search_fts(query)
+        .unwrap()
+        .map(|result| result.unwrap().0)
+        .collect()
+}
+
+/// Full-text search matches tokens, prefixes and phrases, best first.
+#[test]
+fn it_searches_full_text() {
+    let norm = Normal::new_with_nonkeys(":memory:", "genres", "genre", ["about"].iter()).unwrap();
+    let blues = norm.create("blues").unwrap();
+    norm.notate(blues, "about", "Delta and Chicago styles")
+        .unwrap();
+    norm.enable_fts(["about"].iter()).unwrap();
+    let bossa = norm.create("Bossa Nova").unwrap();
+    let jazz = norm.create("jazz").unwrap();
+    norm.notate(jazz, "about", "Jazz from New Orleans, jazz standards")
+        .unwrap();
+    let acid = norm.create("acid jazz").unwrap();
+
+    assert_eq!(fts_ids(&norm, "chicago"), vec![blues]);
+    assert_eq!(fts_ids(&norm, "jazz"), vec![jazz, acid]);
+    assert_eq!(fts_ids(&norm, "nov*"), vec![bossa]);
+    assert_eq!(fts_ids(&norm, "\"bossa nova\""), vec![bossa]);
+    assert_eq!(fts_ids(&norm, "\"nova bossa\""), Vec::<i64>::new());
+    assert_eq!(
+        norm.search_fts("bossa").unwrap().next().unwrap().unwrap(),
+        (bossa, "Bossa Nova".to_string())
+    );
+    assert!(norm.search_fts("\"bossa").unwrap().next().unwrap().is_err());
+}
+
+/// The full-text index follows renames and deletes.
+#[test]
+fn it_syncs_full_text_index() {
+    let norm = new_table().unwrap();
+    norm.enable_fts(std::iter::empty::<&str>()).unwrap();
+    norm.enable_fts(std::iter::empty::<&str>()).unwrap();
+    let id = norm.create("bilbo baggins").unwrap();
+    norm.rename(id, "frodo baggins").unwrap();
+    assert_eq!(fts_ids(&norm, "bilbo"), Vec::<i64>::new());
+    assert_eq!(fts_ids(&norm, "frodo"), vec![id]);
+    norm.delete(id, OnDelete::Restrict).unwrap();
+    assert_eq!(fts_ids(&norm, "baggins"), Vec::<i64>::new());
+}
+
+/// Searching without an index, or changing its columns, is reported.
+#[test]
+fn error_on_missing_full_text_index() {
+    let norm = Normal::new_with_nonkeys(":memory:", "names", "name", ["address"].iter()).unwrap();
+    assert!(matches!(
+        norm.search_fts("bilbo").err(),
+        Some(NormalError::SchemaMismatch(_))
+    ));
+    assert!(norm.enable_fts(["mantra"].iter()).is_err());
+    norm.enable_fts(std::iter::empty::<&str>()).unwrap();
+    assert!(matches!(
+        norm.enable_fts(["address"].iter()),
+        Err(NormalError::SchemaMismatch(_))
+    ));
+}
+
+/// Fuzzy lookups find misspelled values, best first.
+#[test]
+fn it_looks_up_fuzzy() {
+    let norm = Normal::new_with_policy(
+        ":memory:",
+        "genres",
+        "genre",
+        NormalizationPolicy::insensitive(None),
+    )
+    .unwrap();
+    let blues = norm.create("blues").unwrap();
+    norm.enable_fuzzy().unwrap();
+    norm.enable_fuzzy().unwrap();
+    let bluegrass = norm.create("Bluegrass").unwrap();
+    norm.create("jazz").unwrap();
+
+    let matches = norm.lookup_fuzzy("blugrass", 0.3, 10).unwrap();
+    assert_eq!(matches[0].0, bluegrass);
+    assert_eq!(matches[0].1, "bluegrass");
+    assert!(matches[0].2 > 0.5);
+    assert!(matches.iter().all(|(id, _, _)| *id != 3));
+
+    let loose = norm.lookup_fuzzy("blues grass", 0.1, 10).unwrap();
+    let ids: Vec<i64> = loose.iter().map(|(id, _, _)| *id).collect();
+    assert!(ids.contains(&blues) && ids.contains(&bluegrass));
+    assert_eq!(norm.lookup_fuzzy("blues grass", 0.1, 1).unwrap().len(), 1);
+    assert_eq!(norm.lookup_fuzzy("BLUES", 0.9, 10).unwrap()[0].0, blues);
+    assert!(norm.lookup_fuzzy("bl", 0.0, 10).unwrap().is_empty());
+
+    norm.rename(bluegrass, "newgrass").unwrap();
+    assert!(norm.lookup_fuzzy("blugrass", 0.5, 10).unwrap().is_empty());
+}
+
+/// Fuzzy lookups require the trigram index.
+#[test]
+fn error_on_missing_trigram_index() {
+    let norm = new_table().unwrap();
+    let err = norm.lookup_fuzzy("bilbo", 0.5, 10).unwrap_err();
+    assert!(matches!(err, NormalError::SchemaMismatch(_)));
+    assert_eq!(err.to_string(), "missing trigram index for table names");
+}
+
+fn mode_keys(norm: &Normal, value: &str, mode: SearchMode) -> Vec<String> {
+    norm.search_with_mode(value, mode)
+        .unwrap()
+        .map(|result| result.unwrap().1)
+        .collect()
+}
+
+/// Each search mode matches its own syntax.
+#[test]
+fn it_searches_with_modes() {
+    let norm = new_table().unwrap();
+    for name in ["Bilbo", "bilbo", "Frodo", "bilbo baggins", "b*"].iter() {
+        norm.create(name).unwrap();
+    }
+    assert_eq!(
+        mode_keys(&norm, "bilbo%", SearchMode::Like),
+        ["Bilbo", "bilbo", "bilbo baggins"]
+    );
+    assert_eq!(
+        mode_keys(&norm, "b*", SearchMode::Glob),
+        ["bilbo", "bilbo baggins", "b*"]
+    );
+    assert_eq!(mode_keys(&norm, "b*", SearchMode::Exact), ["b*"]);
+    assert_eq!(
+        mode_keys(&norm, "bilbo", SearchMode::Prefix),
+        ["bilbo", "bilbo baggins"]
+    );
+    assert_eq!(mode_keys(&norm, "", SearchMode::Prefix).len(), 5);
+    assert_eq!(
+        mode_keys(&norm, "^[A-Z]\\w+o$", SearchMode::Regex),
+        ["Bilbo", "Frodo"]
+    );
+    assert!(norm
+        .search_with_mode("(", SearchMode::Regex)
+        .unwrap()
+        .next()
+        .unwrap()
+        .is_err());
+}
+
+/// Search results page by cursor in any mode.
+#[test]
+fn it_pages_search_by_cursor() {
+    let norm = new_table().unwrap();
+    for name in ["jazz", "acid jazz", "blues", "jazz fusion"].iter() {
+        norm.create(name).unwrap();
+    }
+    let page = norm
+        .search_paged("jazz", SearchMode::Contains, None, 2)
+        .unwrap();
+    assert_eq!(
+        page.items,
+        [(1, "jazz".to_string()), (2, "acid jazz".to_string())]
+    );
+    let cursor: Cursor = page.next.unwrap().to_string().parse().unwrap();
+    let page = norm
+        .search_paged("jazz", SearchMode::Contains, Some(&cursor), 2)
+        .unwrap();
+    assert_eq!(page.items, [(4, "jazz fusion".to_string())]);
+    assert_eq!(page.next, None);
+    assert!(matches!(
+        "s00".parse::<Cursor>(),
+        Err(NormalError::InvalidCursor(_))
+    ));
+}
+
+/// Wildcard characters may be searched for literally.
+#[test]
+fn it_searches_literal_wildcards() {
+    let norm = new_table().unwrap();
+    for name in ["100%", "1000", "snake_case", "snakescase", "back\\slash"].iter() {
+        norm.create(name).unwrap();
+    }
+    assert_eq!(mode_keys(&norm, "100%", SearchMode::Like), ["100%", "1000"]);
+    assert_eq!(mode_keys(&norm, "0%", SearchMode::Contains), ["100%"]);
+    assert_eq!(
+        mode_keys(&norm, "KE_C", SearchMode::Contains),
+        ["snake_case"]
+    );
+    assert_eq!(
+        mode_keys(&norm, "k\\s", SearchMode::Contains),
+        ["back\\slash"]
+    );
+    assert_eq!(
+        mode_keys(&norm, "snake!_%", SearchMode::LikeEscape('!')),
+        ["snake_case"]
+    );
+    let pattern = format!("{}%", escape_like("100%", '\\'));
     assert_eq!(
-        norm.get_nonkey(id, "superpower").unwrap_err().msg,
-        "missing non-key column superpower"
+        mode_keys(&norm, &pattern, SearchMode::LikeEscape('\\')),
+        ["100%"]
     );
 }
 
+/// Escape characters survive a case-folding policy.
+#[test]
+fn it_searches_escaped_patterns_under_policy() {
+    let norm = Normal::new_with_policy(
+        ":memory:",
+        "rates",
+        "rate",
+        NormalizationPolicy::insensitive(None),
+    )
+    .unwrap();
+    for name in ["100%", "1000"].iter() {
+        norm.create(name).unwrap();
+    }
+    assert_eq!(
+        mode_keys(&norm, "100X%", SearchMode::LikeEscape('X')),
+        ["100%"]
+    );
+}
+
+/// Escaping marks wildcards and the escape character.
+#[test]
+fn it_escapes_like_patterns() {
+    assert_eq!(escape_like("a%b_c!d", '!'), "a!%b!_c!!d");
+    assert_eq!(escape_like("plain", '\\'), "plain");
+}
+
+/// Search modes page through results.
+#[test]
+fn it_pages_search_modes() {
+    let norm = new_table().unwrap();
+    for name in ["jazz", "acid jazz", "blues", "jazz fusion"].iter() {
+        norm.create(name).unwrap();
+    }
+    let mut dest = vec![(0, String::new()); 1];
+    assert_eq!(
+        norm.search_page_with_mode("jazz", SearchMode::Regex, 0, &mut dest)
+            .unwrap(),
+        1
+    );
+    assert_eq!(dest[0], (1, "jazz".to_string()));
+    let mut dest = vec![(0, String::new()); 4];
+    assert_eq!(
+        norm.search_page_with_mode("jazz", SearchMode::Prefix, 1, &mut dest)
+            .unwrap(),
+        1
+    );
+    assert_eq!(dest[0], (4, "jazz fusion".to_string()));
+}
+
+/// Prefix bounds are the next string up.
+#[test]
+fn it_bounds_prefixes() {
+    assert_eq!(prefix_upper_bound("ab").unwrap(), "ac");
+    assert_eq!(prefix_upper_bound("a\u{d7ff}").unwrap(), "a\u{e000}");
+    assert_eq!(prefix_upper_bound("a\u{10ffff}").unwrap(), "b");
+    assert_eq!(prefix_upper_bound("\u{10ffff}"), None);
+    assert_eq!(prefix_upper_bound(""), None);
+}
+
+/// Search modes parse from their names.
+#[test]
+fn it_parses_search_modes() {
+    assert_eq!("regex".parse::<SearchMode>().unwrap(), SearchMode::Regex);
+    assert_eq!(
+        "contains".parse::<SearchMode>().unwrap(),
+        SearchMode::Contains
+    );
+    assert!("fuzzy".parse::<SearchMode>().is_err());
+}
+
 /// Ignores that we have already inserted a value.
 #[test]
 fn it_ignores_duplicate_values() {
@@ -202,13 +700,14 @@ fn it_gets_in_bulk() {
         norm.create(i).unwrap();
     }
 
-    let mut dst = vec![(0, "".to_string()); 2];
-    assert_eq!(norm.get_bulk(&vec![1, 2], &mut dst).unwrap(), 2);
-    assert_eq!(dst[0], (1, "bluegrass".to_string()));
-    assert_eq!(dst[1], (2, "choro".to_string()));
+    assert_eq!(
+        norm.get_bulk(&[2, 1]).unwrap(),
+        vec![Some("choro".to_string()), Some("bluegrass".to_string())]
+    );
+    assert!(norm.get_bulk(&[]).unwrap().is_empty());
 }
 
-/// Gets bulk with missing key.
+/// Gets bulk with missing and repeated keys.
 #[test]
 fn it_gets_in_bulk_with_missing_key() {
     let values = ["bluegrass", "choro", "hip-hop", "jazz", "old-time"];
@@ -217,35 +716,493 @@ fn it_gets_in_bulk_with_missing_key() {
         norm.create(i).unwrap();
     }
 
-    let mut dst = vec![(0, "".to_string()); 2];
-    assert_eq!(norm.get_bulk(&vec![1, 12], &mut dst).unwrap(), 1);
-    assert_eq!(dst[0], (1, "bluegrass".to_string()));
+    assert_eq!(
+        norm.get_bulk(&[1, 12, 1]).unwrap(),
+        vec![
+            Some("bluegrass".to_string()),
+            None,
+            Some("bluegrass".to_string())
+        ]
+    );
 }
 
-/// Gets bulk with undersized destination
+/// Gets bulk across several batches.
 #[test]
-fn it_gets_in_bulk_with_undersized_dest() {
-    let values = ["bluegrass", "choro", "hip-hop", "jazz", "old-time"];
+fn it_gets_in_bulk_across_batches() {
     let norm = new_table().unwrap();
-    for i in values {
-        norm.create(i).unwrap();
-    }
+    let values: Vec<String> = (0..600).map(|i| format!("genre {}", i)).collect();
+    let mut ids = norm.create_bulk(&values).unwrap();
+    ids.push(0);
 
-    let mut dst = vec![(0, "".to_string()); 1];
-    assert_eq!(norm.get_bulk(&vec![1, 2], &mut dst).unwrap(), 1);
-    assert_eq!(dst[0], (1, "bluegrass".to_string()));
+    let found = norm.get_bulk(&ids).unwrap();
+    assert_eq!(found.len(), 601);
+    assert_eq!(found[599].as_deref(), Some("genre 599"));
+    assert_eq!(found[600], None);
 }
 
-/// Gets bulk with oversized destination
+/// Notating a missing column reports a schema mismatch.
 #[test]
-fn it_gets_in_bulk_with_oversized_dest() {
-    let values = ["bluegrass", "choro", "hip-hop", "jazz", "old-time"];
+fn error_on_notate_missing_column() {
     let norm = new_table().unwrap();
-    for i in values {
-        norm.create(i).unwrap();
+    let id = norm.create("bilbo").unwrap();
+    assert!(matches!(
+        norm.notate(id, "superpower", "invisibility"),
+        Err(NormalError::SchemaMismatch(_))
+    ));
+}
+
+/// Opening a file that is not a database reports an error with its source.
+#[test]
+fn error_on_corrupt_file() {
+    use std::error::Error;
+    use std::io::Write;
+
+    let tmpdir = tempdir().unwrap();
+    let db_path = tmpdir.path().join("normal.sqlite3");
+    std::fs::File::create(&db_path)
+        .unwrap()
+        .write_all(&[0xff; 4096])
+        .unwrap();
+
+    let err = Normal::new(db_path.to_str().unwrap(), "names", "name")
+        .err()
+        .unwrap();
+    assert!(matches!(err, NormalError::Sqlite { .. }));
+    assert!(err.source().is_some());
+}
+
+/// Search yields a read failure instead of ending silently.
+#[test]
+fn search_yields_read_errors() {
+    let norm = new_table().unwrap();
+    norm.create("bluegrass").unwrap();
+
+    let pattern = "%".repeat(60000); // exceeds SQLite's LIKE pattern limit.
+    let mut it = norm.search(&pattern).unwrap();
+    assert!(matches!(it.next(), Some(Err(NormalError::Sqlite { .. }))));
+    assert!(it.next().is_none());
+}
+
+/// Creates values in bulk, returning ids in input order.
+#[test]
+fn it_creates_in_bulk() {
+    let norm = new_table().unwrap();
+    norm.create("jazz").unwrap();
+    assert_eq!(
+        norm.create_bulk(["bluegrass", "jazz", "choro"].iter())
+            .unwrap(),
+        vec![2, 1, 3]
+    );
+}
+
+/// Bulk creation rolls back on error.
+#[test]
+fn it_rolls_back_bulk_create() {
+    let norm = new_table().unwrap();
+    norm.conn
+        .writer()
+        .conn()
+        .execute("CREATE TRIGGER no_punk BEFORE INSERT ON names WHEN NEW.name = 'punk' BEGIN SELECT RAISE(ABORT, 'no punk'); END;")
+        .unwrap();
+    assert!(norm.create_bulk(["bluegrass", "punk"].iter()).is_err());
+    assert_eq!(norm.search("%").unwrap().count(), 0);
+}
+
+/// Callers may group operations in a transaction.
+#[test]
+fn it_rolls_back_transactions() {
+    let norm = new_table().unwrap();
+    let tx = norm.transaction().unwrap();
+    norm.create("bluegrass").unwrap();
+    tx.rollback().unwrap();
+    assert_eq!(norm.search("%").unwrap().count(), 0);
+}
+
+/// Reports whether a value was newly created.
+#[test]
+fn it_reports_create_status() {
+    let norm = new_table().unwrap();
+    assert_eq!(
+        norm.create_with_status("jazz").unwrap(),
+        (1, CreateStatus::Created)
+    );
+    assert_eq!(
+        norm.create_with_status("choro").unwrap(),
+        (2, CreateStatus::Created)
+    );
+    assert_eq!(
+        norm.create_with_status("jazz").unwrap(),
+        (1, CreateStatus::Existing)
+    );
+}
+
+/// The fallback for SQLite without RETURNING reports the same ids.
+#[test]
+fn it_inserts_without_returning() {
+    let norm = new_table().unwrap();
+    assert_eq!(norm.insert_or_ignore("jazz", "jazz").unwrap(), Some(1));
+    assert_eq!(norm.insert_or_ignore("choro", "choro").unwrap(), Some(2));
+    assert_eq!(norm.insert_or_ignore("jazz", "jazz").unwrap(), None);
+    assert_eq!(norm.lookup("jazz").unwrap(), Some(1));
+}
+
+/// Tables own their names, so they may be shared with other threads.
+#[test]
+fn it_shares_between_threads() {
+    let table = String::from("genres");
+    let norm = std::sync::Arc::new(Normal::new(":memory:", &table, "genre").unwrap());
+    drop(table);
+
+    let writers: Vec<_> = ["jazz", "choro", "jazz"]
+        .iter()
+        .map(|genre| {
+            let norm = norm.clone();
+            std::thread::spawn(move || norm.create(genre).unwrap())
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
     }
+    assert_eq!(norm.search("%").unwrap().count(), 2);
+}
+
+/// Looks up existing values without inserting missing ones.
+#[test]
+fn it_looks_up_values() {
+    let norm = new_table().unwrap();
+    norm.create("jazz").unwrap();
+    norm.create("choro").unwrap();
+
+    assert_eq!(norm.lookup("choro").unwrap(), Some(2));
+    assert_eq!(norm.lookup("punk").unwrap(), None);
+    assert_eq!(
+        norm.lookup_bulk(&["choro", "punk", "jazz", "choro"])
+            .unwrap(),
+        vec![Some(2), None, Some(1), Some(2)]
+    );
+    assert_eq!(norm.search("%").unwrap().count(), 2);
+}
+
+/// Looks up values across several batches.
+#[test]
+fn it_looks_up_values_across_batches() {
+    let norm = new_table().unwrap();
+    let mut values: Vec<String> = (0..600).map(|i| format!("genre {}", i)).collect();
+    let ids = norm.create_bulk(&values).unwrap();
+    values.push("missing".to_string());
+
+    let found = norm.lookup_bulk(&values).unwrap();
+    assert_eq!(
+        found[..600],
+        ids.into_iter().map(Some).collect::<Vec<_>>()[..]
+    );
+    assert_eq!(found[600], None);
+}
+
+fn new_insensitive_table() -> Normal {
+    let policy = NormalizationPolicy::insensitive(Some("display"));
+    Normal::new_with_policy(":memory:", "genres", "genre", policy).unwrap()
+}
+
+/// Variant spellings share an id under a normalization policy.
+#[test]
+fn it_normalizes_values() {
+    let norm = new_insensitive_table();
+    assert_eq!(norm.create("Jazz").unwrap(), 1);
+    assert_eq!(
+        norm.create_with_status("ｊａｚｚ").unwrap(),
+        (1, CreateStatus::Existing)
+    );
+    assert_eq!(norm.create(" Bossa  Nova").unwrap(), 2);
+    assert_eq!(norm.get(1).unwrap(), "jazz");
+
+    assert_eq!(norm.lookup("JAZZ").unwrap(), Some(1));
+    assert_eq!(
+        norm.lookup_bulk(&["bossa nova", "Jázz", "punk"]).unwrap(),
+        vec![Some(2), Some(1), None]
+    );
+    let found: Vec<i64> = norm
+        .search("BOSSA%")
+        .unwrap()
+        .map(|pair| pair.unwrap().0)
+        .collect();
+    assert_eq!(found, vec![2]);
+}
 
-    let mut dst = vec![(0, "".to_string()); 4];
-    assert_eq!(norm.get_bulk(&vec![1, 2], &mut dst).unwrap(), 2);
-    assert_eq!(dst[0], (1, "bluegrass".to_string()));
+/// The display column keeps the first spelling of a value.
+#[test]
+fn it_keeps_display_spelling() {
+    let norm = new_insensitive_table();
+    let id = norm.create("Bossa Nova").unwrap();
+    norm.create("bossa nova").unwrap();
+    assert_eq!(norm.get_display(id).unwrap(), "Bossa Nova");
+    assert!(matches!(
+        norm.get_display(id + 1),
+        Err(NormalError::NotFound(_))
+    ));
+
+    let plain = new_table().unwrap();
+    let id = plain.create("Choro").unwrap();
+    assert_eq!(plain.get_display(id).unwrap(), "Choro");
+}
+
+/// The fallback for SQLite without RETURNING also keeps the display spelling.
+#[test]
+fn it_keeps_display_spelling_without_returning() {
+    let norm = new_insensitive_table();
+    assert_eq!(norm.insert_or_ignore("jazz", "Jazz").unwrap(), Some(1));
+    assert_eq!(norm.insert_or_ignore("jazz", "JAZZ").unwrap(), None);
+    assert_eq!(norm.get_display(1).unwrap(), "Jazz");
+}
+
+/// Aliases resolve to the id of their value.
+#[test]
+fn it_resolves_aliases() {
+    let norm = new_insensitive_table();
+    let rnb = norm.create("R&B").unwrap();
+    norm.add_alias(rnb, "Rhythm and Blues").unwrap();
+    norm.add_alias(rnb, "RnB").unwrap();
+    norm.add_alias(rnb, "rnb").unwrap(); // already present after normalization.
+
+    assert_eq!(norm.aliases(rnb).unwrap(), vec!["rhythm and blues", "rnb"]);
+    assert_eq!(
+        norm.create_with_status("RNB").unwrap(),
+        (rnb, CreateStatus::Existing)
+    );
+    assert_eq!(norm.lookup("rhythm  and blues").unwrap(), Some(rnb));
+    assert_eq!(
+        norm.lookup_bulk(&["jazz", "rnb", "r&b"]).unwrap(),
+        vec![None, Some(rnb), Some(rnb)]
+    );
+
+    assert!(norm.remove_alias("RnB").unwrap());
+    assert!(!norm.remove_alias("RnB").unwrap());
+    assert_eq!(norm.lookup("rnb").unwrap(), None);
+}
+
+/// Inserting an alias adds no row and uses up no id.
+#[test]
+fn it_skips_aliases_on_insert() {
+    let norm = new_table().unwrap();
+    let rnb = norm.create("r&b").unwrap();
+    norm.add_alias(rnb, "rnb").unwrap();
+    assert_eq!(norm.insert_returning("rnb", "rnb").unwrap(), None);
+    assert_eq!(norm.insert_or_ignore("rnb", "rnb").unwrap(), None);
+    assert_eq!(norm.create("soul").unwrap(), rnb + 1);
+}
+
+/// Aliases may not take a spelling used by another value.
+#[test]
+fn it_reports_alias_conflicts() {
+    let norm = new_table().unwrap();
+    let jazz = norm.create("jazz").unwrap();
+    let choro = norm.create("choro").unwrap();
+    norm.add_alias(jazz, "bop").unwrap();
+
+    assert!(matches!(
+        norm.add_alias(choro, "jazz"),
+        Err(NormalError::Conflict { id, .. }) if id == jazz
+    ));
+    assert!(matches!(
+        norm.add_alias(choro, "bop"),
+        Err(NormalError::Conflict { id, .. }) if id == jazz
+    ));
+    assert!(matches!(
+        norm.add_alias(17, "samba"),
+        Err(NormalError::NotFound(_))
+    ));
+    assert!(norm.aliases(choro).unwrap().is_empty());
+}
+
+/// Searches optionally match aliases, reporting each value once.
+#[test]
+fn it_searches_aliases() {
+    let norm = new_table().unwrap();
+    let rnb = norm.create("r&b").unwrap();
+    norm.create("rock").unwrap();
+    norm.add_alias(rnb, "rhythm and blues").unwrap();
+    norm.add_alias(rnb, "rnb").unwrap();
+
+    assert_eq!(norm.search("r%b%").unwrap().count(), 1);
+    let found: Vec<(i64, String)> = norm
+        .search_with_aliases("r%b%")
+        .unwrap()
+        .collect::<Result<_, NormalError>>()
+        .unwrap();
+    assert_eq!(found, vec![(rnb, "r&b".to_string())]);
+    assert_eq!(norm.search_with_aliases("rhythm%").unwrap().count(), 1);
+}
+
+/// Merging rewrites registered pairs and keeps the loser as an alias.
+#[test]
+fn it_merges_values() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let tags = db.id_pairs("album_genres", "album", "genre").unwrap();
+    genres.add_reference(&tags, PairColumn::Right).unwrap();
+
+    let rnb = genres.create("r&b").unwrap();
+    let rhythm = genres.create("rhythm and blues").unwrap();
+    genres.add_alias(rhythm, "rnb").unwrap();
+    tags.insert(1, rnb).unwrap();
+    tags.insert(1, rhythm).unwrap(); // duplicates album 1's tag after merging.
+    tags.insert(2, rhythm).unwrap();
+
+    genres.merge(rnb, rhythm).unwrap();
+    assert!(matches!(genres.get(rhythm), Err(NormalError::NotFound(_))));
+    assert_eq!(
+        genres.aliases(rnb).unwrap(),
+        vec!["rhythm and blues", "rnb"]
+    );
+    assert_eq!(genres.lookup("rhythm and blues").unwrap(), Some(rnb));
+    assert_eq!(tags.invert(rnb).unwrap().count(), 2);
+    assert_eq!(tags.invert(rhythm).unwrap().count(), 0);
+}
+
+/// A merged id is not given to values created later.
+#[test]
+fn it_retires_merged_ids() {
+    let norm = new_table().unwrap();
+    let ids = norm.create_bulk(["a", "b", "c"].iter()).unwrap();
+    norm.merge(ids[0], ids[2]).unwrap();
+    assert_eq!(norm.create("a").unwrap(), ids[0]);
+    assert_eq!(norm.create("c").unwrap(), ids[0]);
+    let e = norm.create("e").unwrap();
+    assert_eq!(e, 4);
+    assert!(matches!(norm.get(ids[2]), Err(NormalError::NotFound(_))));
+}
+
+/// Tables created before ids were declared still open and list non-keys.
+#[test]
+fn it_opens_tables_without_id_column() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    genres
+        .conn
+        .writer()
+        .conn()
+        .execute("CREATE TABLE old_genres (genre TEXT UNIQUE, note TEXT)")
+        .unwrap();
+    let old = db.normal("old_genres", "genre").unwrap();
+    assert_eq!(old.create("jazz").unwrap(), 1);
+    assert_eq!(old.get_nonkeys().unwrap(), vec!["note"]);
+    assert!(genres.get_nonkeys().unwrap().is_empty());
+}
+
+/// Merging a missing id reports it and changes nothing.
+#[test]
+fn it_rejects_merging_missing_ids() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let tags = db.id_pairs("album_genres", "album", "genre").unwrap();
+    genres.add_reference(&tags, PairColumn::Right).unwrap();
+    let jazz = genres.create("jazz").unwrap();
+    tags.insert(1, jazz).unwrap();
+
+    assert!(matches!(
+        genres.merge(17, jazz),
+        Err(NormalError::NotFound(_))
+    ));
+    assert_eq!(genres.get(jazz).unwrap(), "jazz");
+    assert_eq!(tags.invert(jazz).unwrap().count(), 1);
+}
+
+/// References must share the table's connection.
+#[test]
+fn it_rejects_references_from_other_databases() {
+    let genres = new_table().unwrap();
+    let tags = IdPairs::new(":memory:", "album_genres", "album", "genre").unwrap();
+    assert!(matches!(
+        genres.add_reference(&tags, PairColumn::Right),
+        Err(NormalError::SchemaMismatch(_))
+    ));
+}
+
+/// Splitting turns an alias back into a value.
+#[test]
+fn it_splits_aliases() {
+    let norm = new_table().unwrap();
+    let jazz = norm.create("jazz").unwrap();
+    let bop = norm.create("bop").unwrap();
+    norm.merge(jazz, bop).unwrap();
+
+    let bop = norm.split("bop").unwrap();
+    assert_eq!(norm.get(bop).unwrap(), "bop");
+    assert!(norm.aliases(jazz).unwrap().is_empty());
+    assert!(matches!(norm.split("bop"), Err(NormalError::NotFound(_))));
+}
+
+/// Renaming respells a value, reporting spellings already taken.
+#[test]
+fn it_renames_values() {
+    let norm = new_insensitive_table();
+    let jazz = norm.create("Jaz").unwrap();
+    let choro = norm.create("Choro").unwrap();
+    norm.add_alias(choro, "chorinho").unwrap();
+    norm.add_alias(jazz, "Jazz Music").unwrap();
+
+    norm.rename(jazz, "Jazz").unwrap();
+    assert_eq!(norm.get(jazz).unwrap(), "jazz");
+    assert_eq!(norm.get_display(jazz).unwrap(), "Jazz");
+    assert_eq!(norm.lookup("jaz").unwrap(), None);
+
+    assert!(matches!(
+        norm.rename(jazz, "CHORO"),
+        Err(NormalError::Conflict { id, .. }) if id == choro
+    ));
+    assert!(matches!(
+        norm.rename(jazz, "chorinho"),
+        Err(NormalError::Conflict { id, .. }) if id == choro
+    ));
+    assert!(matches!(
+        norm.rename(17, "samba"),
+        Err(NormalError::NotFound(_))
+    ));
+
+    norm.rename(jazz, "jazz music").unwrap(); // an alias becomes the value.
+    assert_eq!(norm.get(jazz).unwrap(), "jazz music");
+    assert!(norm.aliases(jazz).unwrap().is_empty());
+}
+
+/// Deleting refuses referenced ids unless cascading.
+#[test]
+fn it_deletes_values() {
+    let db = Database::open(":memory:").unwrap();
+    let genres = db.normal("genres", "genre").unwrap();
+    let tags = db.id_pairs("album_genres", "album", "genre").unwrap();
+    genres.add_reference(&tags, PairColumn::Right).unwrap();
+
+    let jazz = genres.create("jazz").unwrap();
+    let choro = genres.create("choro").unwrap();
+    genres.add_alias(jazz, "bop").unwrap();
+    tags.insert(1, jazz).unwrap();
+    tags.insert(1, choro).unwrap();
+
+    assert!(matches!(
+        genres.delete(jazz, OnDelete::Restrict),
+        Err(NormalError::Referenced { id, .. }) if id == jazz
+    ));
+    assert_eq!(genres.get(jazz).unwrap(), "jazz");
+
+    genres.delete(jazz, OnDelete::Cascade).unwrap();
+    assert!(matches!(genres.get(jazz), Err(NormalError::NotFound(_))));
+    assert_eq!(genres.lookup("bop").unwrap(), None);
+    let albums: Vec<i64> = tags.get(1).unwrap().map(Result::unwrap).collect();
+    assert_eq!(albums, vec![choro]);
+
+    assert!(matches!(
+        genres.delete(jazz, OnDelete::Cascade),
+        Err(NormalError::NotFound(_))
+    ));
+}
+
+/// A deleted id is not given to values created later.
+#[test]
+fn it_retires_deleted_ids() {
+    let norm = new_table().unwrap();
+    norm.create("a").unwrap();
+    let b = norm.create("b").unwrap();
+    norm.delete(b, OnDelete::Restrict).unwrap();
+    assert_eq!(norm.create("c").unwrap(), b + 1);
+    assert!(matches!(norm.get(b), Err(NormalError::NotFound(_))));
 }
@@ -1,4 +1,4 @@
-use normal::Normal;
+use normal::{Database, Identifier, Normal, NormalError, OnDelete, PairColumn, SearchMode};
 use regex::Regex;
 use std::path::PathBuf;
 use std::process::exit;
@@ -7,91 +7,193 @@ use structopt::StructOpt;
 #[derive(Debug, StructOpt)]
 #[structopt(name = "normal-util", about = "Normalization table utility routines.")]
 struct Opt {
+    /// Remove pairs referring to a deleted id instead of refusing to delete it.
+    #[structopt(long)]
+    cascade: bool,
+
     #[structopt(short, long)]
-    column: String,
+    column: Identifier<'static>,
 
     #[structopt(parse(from_os_str))]
     db: PathBuf,
 
+    #[structopt(short, long)]
+    delete: Option<i64>,
+
+    #[structopt(short, long)]
+    escape: Option<char>,
+
     #[structopt(short, long)]
     get: Option<i64>,
 
     #[structopt(short, long)]
     insert: Option<String>,
 
+    #[structopt(short, long, default_value = "like")]
+    mode: SearchMode,
+
     #[structopt(short, long)]
     note: Option<String>,
 
+    /// A pairs table checked by --delete, as table:left:right:column where
+    /// column is left or right; may be repeated.
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_reference))]
+    reference: Vec<Reference>,
+
+    #[structopt(short, long)]
+    rename: Option<String>,
+
     #[structopt(short, long)]
     search: Option<String>,
 
     #[structopt(short, long)]
-    table: String,
+    table: Identifier<'static>,
 }
 
 pub fn main() {
     let opt = Opt::from_args();
-    let normal = Normal::new(
-        opt.db.as_os_str().to_str().unwrap(),
-        opt.table.as_str(),
-        opt.column.as_str(),
-    )
-    .unwrap();
+    let normal = match open(&opt) {
+        Ok(normal) => normal,
+        Err(err) => {
+            println!("error: {}", err);
+            exit(1);
+        }
+    };
 
     // insert key-value
-    opt.insert
-        .map(|key| normal.create(key.as_str()))
-        .map(|insert_result| match insert_result {
+    if let Some(key) = opt.insert {
+        match normal.create(key.as_str()) {
             Ok(id) => println!("{}", id),
             Err(err) => {
-                println!("error: {}", err.msg);
+                println!("error: {}", err);
                 exit(1);
             }
-        });
+        }
+    }
+
+    // rename id, new value
+    if let Some(id_value_str) = opt.rename {
+        let (id, value) = parse_rename(id_value_str);
+        if let Err(err) = normal.rename(id, value.as_ref()) {
+            println!("error: {}", err);
+            exit(1);
+        }
+    }
 
     // get key by id
-    opt.get
-        .map(|id| normal.get(id))
-        .map(|key_result| match key_result {
+    if let Some(id) = opt.get {
+        match normal.get(id) {
             Ok(key) => println!("{}", key),
             Err(err) => {
-                println!("error: {}", err.msg);
+                println!("error: {}", err);
                 exit(1);
             }
-        });
+        }
+    }
 
     // notate id, column, text
-    opt.note
-        .map(|id_col_text_str| parse_notate(id_col_text_str))
-        .map(|id_col_text_str| match id_col_text_str {
-            (id, col, note) => match normal.notate(id, col.as_ref(), note.as_ref()) {
-                Ok(()) => (),
-                Err(err) => {
-                    println!("error: {}", err.msg);
-                    exit(1);
-                }
-            },
-        });
+    if let Some(id_col_text_str) = opt.note {
+        let (id, col, note) = parse_notate(id_col_text_str);
+        if let Err(err) = normal.notate(id, col.as_str(), note.as_str()) {
+            println!("error: {}", err);
+            exit(1);
+        }
+    }
+
+    // delete by id
+    if let Some(id) = opt.delete {
+        let on_delete = if opt.cascade {
+            OnDelete::Cascade
+        } else {
+            OnDelete::Restrict
+        };
+        if let Err(err) = normal.delete(id, on_delete) {
+            println!("error: {}", err);
+            exit(1);
+        }
+    }
 
-    // substring search
-    opt.search.map(|search| normal.search(search.as_str())).map(
-        |search_result| match search_result {
+    // search by like, glob, exact, prefix, contains or regex mode
+    if let Some(search) = opt.search {
+        let mode = match (opt.mode, opt.escape) {
+            (SearchMode::Like, Some(escape)) => SearchMode::LikeEscape(escape),
+            (mode, None) => mode,
+            (_, Some(_)) => {
+                println!("error: --escape applies only to like mode");
+                exit(1);
+            }
+        };
+        match normal.search_with_mode(search.as_str(), mode) {
             Ok(i) => {
                 let mut ip = i.peekable();
-                if None == ip.peek() {
+                if ip.peek().is_none() {
                     println!("no key");
                     exit(2);
                 }
                 for i in ip {
-                    println!("{}: {}", i.0, i.1)
+                    match i {
+                        Ok((id, key)) => println!("{}: {}", id, key),
+                        Err(err) => {
+                            println!("error: {}", err);
+                            exit(1);
+                        }
+                    }
                 }
             }
             Err(err) => {
-                println!("error: {}", err.msg);
+                println!("error: {}", err);
                 exit(1);
             }
-        },
-    );
+        }
+    }
+}
+
+/// A pairs table column holding ids of the normalization table.
+#[derive(Debug, PartialEq)]
+struct Reference {
+    table: Identifier<'static>,
+    left: Identifier<'static>,
+    right: Identifier<'static>,
+    column: PairColumn,
+}
+
+/// Open the table, registering the referring pairs tables.
+fn open(opt: &Opt) -> Result<Normal, NormalError> {
+    let db = Database::open(opt.db.as_os_str().to_str().unwrap())?;
+    let normal = db.normal(opt.table.as_str(), opt.column.as_str())?;
+    for reference in &opt.reference {
+        let pairs = db.id_pairs(
+            reference.table.as_str(),
+            reference.left.as_str(),
+            reference.right.as_str(),
+        )?;
+        normal.add_reference(&pairs, reference.column)?;
+    }
+    Ok(normal)
+}
+
+/// Break up the command-line argument naming a pairs table as
+/// "table:left:right:column".
+fn parse_reference(reference: &str) -> Result<Reference, String> {
+    let parts: Vec<&str> = reference.split(':').collect();
+    if parts.len() != 4 {
+        return Err(format!(
+            "expected table:left:right:column, got {:?}",
+            reference
+        ));
+    }
+    let identifier = |name: &str| Identifier::new(name.to_string()).map_err(|e| e.to_string());
+    let column = match parts[3] {
+        "left" => PairColumn::Left,
+        "right" => PairColumn::Right,
+        column => return Err(format!("expected left or right, got {:?}", column)),
+    };
+    Ok(Reference {
+        table: identifier(parts[0])?,
+        left: identifier(parts[1])?,
+        right: identifier(parts[2])?,
+        column,
+    })
 }
 
 /// Break up the command-line argument to notate an entry as "id column lots of note text following...."
@@ -105,6 +207,16 @@ fn parse_notate(id_col_text_str: String) -> (i64, String, String) {
     )
 }
 
+/// Break up the command-line argument to rename an entry as "id new value...."
+fn parse_rename(id_value_str: String) -> (i64, String) {
+    let re = Regex::new(r"(\d+)\s+(.*)").unwrap();
+    let tokens = re.captures_iter(id_value_str.as_str()).next().unwrap();
+    (
+        tokens.get(1).unwrap().as_str().parse::<i64>().unwrap(),
+        tokens.get(2).unwrap().as_str().to_string(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,4 +228,27 @@ mod tests {
             (1, "note".to_string(), "nota bene".to_string())
         );
     }
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(
+            parse_reference("album_genres:album:genre:right").unwrap(),
+            Reference {
+                table: Identifier::new("album_genres").unwrap(),
+                left: Identifier::new("album").unwrap(),
+                right: Identifier::new("genre").unwrap(),
+                column: PairColumn::Right,
+            }
+        );
+        assert!(parse_reference("album_genres:album:genre").is_err());
+        assert!(parse_reference("album_genres:album:genre:middle").is_err());
+    }
+
+    #[test]
+    fn test_parse_rename() {
+        assert_eq!(
+            parse_rename("12 bossa nova".to_string()),
+            (12, "bossa nova".to_string())
+        );
+    }
 }
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normalization forms applied to values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnicodeForm {
+    /// Canonical composition, e.g. "e" + U+0301 becomes "é".
+    Nfc,
+    /// Compatibility composition, e.g. full-width "ｊａｚｚ" becomes "jazz".
+    Nfkc,
+}
+
+/// How values are normalized before they are stored or looked up.
+///
+/// The default leaves values untouched.  Open a table with the same policy
+/// each time, since stored values are not renormalized.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NormalizationPolicy {
+    /// Convert to lower case.
+    pub case_fold: bool,
+    /// Apply a Unicode normalization form.
+    pub unicode: Option<UnicodeForm>,
+    /// Remove leading and trailing whitespace.
+    pub trim: bool,
+    /// Replace runs of whitespace with a single space.
+    pub collapse_whitespace: bool,
+    /// Remove accents and other combining marks, e.g. "é" becomes "e".
+    pub strip_diacritics: bool,
+    /// Keep the spelling a value was first created with in this column.
+    pub display_column: Option<String>,
+}
+
+impl NormalizationPolicy {
+    /// Apply every normalization, keeping the first spelling of each value
+    /// in `display_column`.
+    pub fn insensitive(display_column: Option<&str>) -> NormalizationPolicy {
+        NormalizationPolicy {
+            case_fold: true,
+            unicode: Some(UnicodeForm::Nfkc),
+            trim: true,
+            collapse_whitespace: true,
+            strip_diacritics: true,
+            display_column: display_column.map(|column| column.to_string()),
+        }
+    }
+
+    /// Normalize a value, or a search pattern.
+    pub fn apply<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        if self.strip_diacritics {
+            // Decompose first so that accents become separate marks.
+            value = Cow::Owned(value.nfd().filter(|c| !is_combining_mark(*c)).collect());
+        }
+        match self.unicode {
+            Some(UnicodeForm::Nfc) => value = Cow::Owned(value.nfc().collect()),
+            Some(UnicodeForm::Nfkc) => value = Cow::Owned(value.nfkc().collect()),
+            None => (),
+        }
+        if self.case_fold {
+            value = Cow::Owned(value.to_lowercase());
+        }
+        if self.collapse_whitespace {
+            value = Cow::Owned(collapse_whitespace(&value));
+        }
+        if self.trim {
+            value = match value {
+                Cow::Borrowed(value) => Cow::Borrowed(value.trim()),
+                Cow::Owned(value) => Cow::Owned(value.trim().to_string()),
+            };
+        }
+        value
+    }
+}
+
+/// Replace each run of whitespace with a single space.
+fn collapse_whitespace(value: &str) -> String {
+    let mut collapsed = String::with_capacity(value.len());
+    let mut in_space = false;
+    for c in value.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+#[path = "./normalization_test.rs"]
+mod normalization_test;
@@ -0,0 +1,55 @@
+use super::*;
+
+/// The default policy leaves values untouched.
+#[test]
+fn it_keeps_values_by_default() {
+    let policy = NormalizationPolicy::default();
+    assert!(matches!(policy.apply(" Jazz "), Cow::Borrowed(" Jazz ")));
+}
+
+/// Case, width, accents and spacing variants normalize to one value.
+#[test]
+fn it_normalizes_variants() {
+    let policy = NormalizationPolicy::insensitive(None);
+    for variant in ["Jazz", "jazz", "ｊａｚｚ", "  JAZZ\t", "jázz"].iter() {
+        assert_eq!(policy.apply(variant), "jazz");
+    }
+    assert_eq!(policy.apply(" Bossa \n  Nova "), "bossa nova");
+}
+
+/// Each option applies on its own.
+#[test]
+fn it_applies_options_separately() {
+    let nfc = NormalizationPolicy {
+        unicode: Some(UnicodeForm::Nfc),
+        ..NormalizationPolicy::default()
+    };
+    assert_eq!(nfc.apply("Cafe\u{301}"), "Café");
+    assert_eq!(nfc.apply("ｊａｚｚ"), "ｊａｚｚ");
+
+    let trim = NormalizationPolicy {
+        trim: true,
+        ..NormalizationPolicy::default()
+    };
+    assert_eq!(trim.apply("  a  b "), "a  b");
+
+    let collapse = NormalizationPolicy {
+        collapse_whitespace: true,
+        ..NormalizationPolicy::default()
+    };
+    assert_eq!(collapse.apply("  a  b "), " a b ");
+
+    let strip = NormalizationPolicy {
+        strip_diacritics: true,
+        ..NormalizationPolicy::default()
+    };
+    assert_eq!(strip.apply("Crème Brûlée"), "Creme Brulee");
+}
+
+/// Search wildcards survive normalization.
+#[test]
+fn it_keeps_wildcards() {
+    let policy = NormalizationPolicy::insensitive(None);
+    assert_eq!(policy.apply("Blue%"), "blue%");
+    assert_eq!(policy.apply("_AZZ"), "_azz");
+}
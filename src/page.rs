@@ -0,0 +1,142 @@
+use crate::NormalError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a page ends, so that the next page resumes after it.
+///
+/// Cursors are opaque tokens of URL-safe characters, so they may be handed
+/// to HTTP clients and parsed back with `str::parse`.  They hold only the
+/// position of the last item, and are checked to belong to the same kind of
+/// listing; a forged token can only resume at another position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    kind: CursorKind,
+    first: i64,
+    second: i64,
+}
+
+/// The listings cursors page through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CursorKind {
+    /// `Normal::search_paged`, after an id.
+    Search,
+    /// `IdPairs::get_paged`, after a key and value.
+    Get,
+    /// `IdPairs::invert_paged`, after a value and key.
+    Invert,
+    /// `IdPairs::pairs_paged` by key, after a key and value.
+    Left,
+    /// `IdPairs::pairs_paged` by value, after a value and key.
+    Right,
+}
+
+impl CursorKind {
+    fn tag(self) -> char {
+        match self {
+            CursorKind::Search => 's',
+            CursorKind::Get => 'g',
+            CursorKind::Invert => 'i',
+            CursorKind::Left => 'l',
+            CursorKind::Right => 'r',
+        }
+    }
+
+    fn from_tag(tag: char) -> Option<CursorKind> {
+        match tag {
+            's' => Some(CursorKind::Search),
+            'g' => Some(CursorKind::Get),
+            'i' => Some(CursorKind::Invert),
+            'l' => Some(CursorKind::Left),
+            'r' => Some(CursorKind::Right),
+            _ => None,
+        }
+    }
+}
+
+impl Cursor {
+    pub(crate) fn new(kind: CursorKind, first: i64, second: i64) -> Cursor {
+        Cursor {
+            kind,
+            first,
+            second,
+        }
+    }
+
+    /// Return the position of a cursor for the listing, or an error if it
+    /// belongs to another one.
+    pub(crate) fn position(&self, kind: CursorKind) -> Result<(i64, i64), NormalError> {
+        if self.kind != kind {
+            return Err(NormalError::InvalidCursor(format!(
+                "cursor {} belongs to another listing",
+                self
+            )));
+        }
+        Ok((self.first, self.second))
+    }
+}
+
+/// A tag followed by both numbers as 16 hex digits.
+const TOKEN_LEN: usize = 33;
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{:016x}{:016x}",
+            self.kind.tag(),
+            self.first as u64,
+            self.second as u64
+        )
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = NormalError;
+
+    fn from_str(token: &str) -> Result<Cursor, NormalError> {
+        let invalid = || NormalError::InvalidCursor(format!("malformed cursor {:?}", token));
+        if token.len() != TOKEN_LEN
+            || !token.is_ascii()
+            || !token[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(invalid());
+        }
+        let kind = token
+            .chars()
+            .next()
+            .and_then(CursorKind::from_tag)
+            .ok_or_else(invalid)?;
+        let first = u64::from_str_radix(&token[1..17], 16).map_err(|_| invalid())?;
+        let second = u64::from_str_radix(&token[17..], 16).map_err(|_| invalid())?;
+        Ok(Cursor::new(kind, first as i64, second as i64))
+    }
+}
+
+/// Items of one page, and the cursor to the next page if there may be one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<Cursor>,
+}
+
+impl<T> Page<T> {
+    /// Make a page from up to `limit + 1` items read, where an extra item
+    /// shows that another page follows.
+    pub(crate) fn from_items(
+        mut items: Vec<T>,
+        limit: usize,
+        cursor_of: impl Fn(&T) -> Cursor,
+    ) -> Page<T> {
+        let next = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(cursor_of)
+        } else {
+            None
+        };
+        Page { items, next }
+    }
+}
+
+#[cfg(test)]
+#[path = "./page_test.rs"]
+mod page_test;
@@ -0,0 +1,48 @@
+use super::*;
+
+/// Cursors round-trip through URL-safe tokens.
+#[test]
+fn it_round_trips_tokens() {
+    let cursor = Cursor::new(CursorKind::Left, -1, 42);
+    let token = cursor.to_string();
+    assert_eq!(token, "lffffffffffffffff000000000000002a");
+    assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+    assert_eq!(token.parse::<Cursor>().unwrap(), cursor);
+    assert_eq!(cursor.position(CursorKind::Left).unwrap(), (-1, 42));
+}
+
+/// Malformed tokens and cursors of other listings are rejected.
+#[test]
+fn error_on_invalid_tokens() {
+    for token in [
+        "",
+        "x00000000000000000000000000000000",
+        "l0000000000000000000000000000000",
+        "l+000000000000000000000000000000a",
+        "l0000000000000000000000000000000é",
+        "é0000000000000000000000000000000",
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            token.parse::<Cursor>(),
+            Err(NormalError::InvalidCursor(_))
+        ));
+    }
+    let cursor = Cursor::new(CursorKind::Search, 1, 0);
+    assert!(matches!(
+        cursor.position(CursorKind::Get),
+        Err(NormalError::InvalidCursor(_))
+    ));
+}
+
+/// A page has a next cursor only when more items were read.
+#[test]
+fn it_makes_pages() {
+    let cursor_of = |id: &i64| Cursor::new(CursorKind::Search, *id, 0);
+    let page = Page::from_items(vec![1, 2, 3], 2, cursor_of);
+    assert_eq!(page.items, vec![1, 2]);
+    assert_eq!(page.next, Some(cursor_of(&2)));
+    let page = Page::from_items(vec![1, 2], 2, cursor_of);
+    assert_eq!(page.next, None);
+}
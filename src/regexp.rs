@@ -0,0 +1,113 @@
+use crate::NormalError;
+use regex::Regex;
+use sqlite::Connection;
+use sqlite3_sys as ffi;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+/// Register the `regexp(pattern, value)` function, so that
+/// `value REGEXP pattern` matches with the `regex` crate.
+pub(crate) fn register_regexp(conn: &Connection) -> Result<(), NormalError> {
+    // SAFETY: the name is NUL-terminated and the callback matches the
+    // two-argument signature registered.
+    let code = unsafe {
+        ffi::sqlite3_create_function_v2(
+            conn.as_raw(),
+            b"regexp\0".as_ptr() as *const c_char,
+            2,
+            ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+            ptr::null_mut(),
+            Some(regexp),
+            None,
+            None,
+            None,
+        )
+    };
+    if code == ffi::SQLITE_OK {
+        return Ok(());
+    }
+    Err(NormalError::sqlite(
+        "cannot register regexp function",
+        sqlite::Error {
+            code: Some(code as isize),
+            message: None,
+        },
+    ))
+}
+
+/// Read a text argument, or `None` for NULL.
+///
+/// # Safety
+///
+/// The value must be a valid argument of the running function.
+unsafe fn value_str<'a>(value: *mut ffi::sqlite3_value) -> Option<Result<&'a str, String>> {
+    if ffi::sqlite3_value_type(value) == ffi::SQLITE_NULL {
+        return None;
+    }
+    // Read the text before its length, which it may convert.
+    let text = ffi::sqlite3_value_text(value);
+    let len = ffi::sqlite3_value_bytes(value) as usize;
+    if text.is_null() {
+        return Some(Ok(""));
+    }
+    let bytes = std::slice::from_raw_parts(text, len);
+    Some(std::str::from_utf8(bytes).map_err(|e| e.to_string()))
+}
+
+/// Report an error from the running function.
+///
+/// # Safety
+///
+/// The context must be that of the running function.
+unsafe fn result_error(ctx: *mut ffi::sqlite3_context, msg: &str) {
+    ffi::sqlite3_result_error(ctx, msg.as_ptr() as *const c_char, msg.len() as c_int);
+}
+
+/// Free a pattern compiled by `regexp`.
+extern "C" fn free_regex(regex: *mut c_void) {
+    // SAFETY: only boxed patterns are handed to SQLite with this destructor.
+    unsafe { drop(Box::from_raw(regex as *mut Regex)) }
+}
+
+/// Match a value against a pattern, caching the compiled pattern while the
+/// statement runs with the same one.
+extern "C" fn regexp(
+    ctx: *mut ffi::sqlite3_context,
+    _argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: SQLite passes the two registered arguments, and keeps the
+    // cached pattern alive until it calls `free_regex`.
+    unsafe {
+        let (pattern, value) = (*argv, *argv.add(1));
+        let text = match value_str(value) {
+            Some(Ok(text)) => text,
+            Some(Err(e)) => return result_error(ctx, &e),
+            None => return ffi::sqlite3_result_null(ctx),
+        };
+        let cached = ffi::sqlite3_get_auxdata(ctx, 0) as *const Regex;
+        if !cached.is_null() {
+            return ffi::sqlite3_result_int(ctx, c_int::from((*cached).is_match(text)));
+        }
+        let regex = match value_str(pattern) {
+            Some(Ok(pattern)) => match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(e) => return result_error(ctx, &e.to_string()),
+            },
+            Some(Err(e)) => return result_error(ctx, &e),
+            None => return ffi::sqlite3_result_null(ctx),
+        };
+        ffi::sqlite3_result_int(ctx, c_int::from(regex.is_match(text)));
+        // SQLite frees the pattern at once if it cannot keep it.
+        ffi::sqlite3_set_auxdata(
+            ctx,
+            0,
+            Box::into_raw(Box::new(regex)) as *mut c_void,
+            Some(free_regex),
+        );
+    }
+}
+
+#[cfg(test)]
+#[path = "./regexp_test.rs"]
+mod regexp_test;
@@ -0,0 +1,49 @@
+use super::*;
+
+fn query(conn: &Connection, sql: &str) -> Result<Option<i64>, sqlite::Error> {
+    let mut statement = conn.prepare(sql)?;
+    statement.next()?;
+    statement.read::<Option<i64>>(0)
+}
+
+/// Values match patterns with the regex crate's syntax.
+#[test]
+fn it_matches_patterns() {
+    let conn = sqlite::open(":memory:").unwrap();
+    register_regexp(&conn).unwrap();
+    assert_eq!(
+        query(&conn, "SELECT 'jazz' REGEXP '^ja+z{2}$'").unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        query(&conn, "SELECT 'blues' REGEXP '\\d'").unwrap(),
+        Some(0)
+    );
+    assert_eq!(query(&conn, "SELECT NULL REGEXP 'a'").unwrap(), None);
+}
+
+/// Patterns are reused across rows.
+#[test]
+fn it_matches_rows() {
+    let conn = sqlite::open(":memory:").unwrap();
+    register_regexp(&conn).unwrap();
+    conn.execute("CREATE TABLE genres (genre TEXT); INSERT INTO genres VALUES ('jazz'), ('acid jazz'), ('blues');")
+        .unwrap();
+    assert_eq!(
+        query(
+            &conn,
+            "SELECT COUNT(*) FROM genres WHERE genre REGEXP 'jazz$'"
+        )
+        .unwrap(),
+        Some(2)
+    );
+}
+
+/// Invalid patterns report the regex error.
+#[test]
+fn error_on_invalid_pattern() {
+    let conn = sqlite::open(":memory:").unwrap();
+    register_regexp(&conn).unwrap();
+    let err = query(&conn, "SELECT 'jazz' REGEXP '('").unwrap_err();
+    assert!(err.message.unwrap().contains("unclosed group"));
+}
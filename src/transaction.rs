@@ -0,0 +1,68 @@
+use crate::cached_connection::CachedConnection;
+use crate::NormalError;
+use parking_lot::ReentrantMutexGuard;
+
+/// A scoped transaction, rolled back unless committed.
+///
+/// Transactions are implemented with savepoints, so they may be nested,
+/// e.g. a bulk insertion inside a caller's transaction.  The connection is
+/// locked to the current thread until the transaction finishes.
+pub struct Transaction<'a> {
+    conn: ReentrantMutexGuard<'a, CachedConnection>,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn begin(
+        conn: ReentrantMutexGuard<'a, CachedConnection>,
+    ) -> Result<Transaction<'a>, NormalError> {
+        conn.conn()
+            .execute("SAVEPOINT normal_transaction;")
+            .map_err(|e| NormalError::sqlite("cannot begin transaction", e))?;
+        Ok(Transaction {
+            conn,
+            finished: false,
+        })
+    }
+
+    /// Make the changes since the transaction began permanent.
+    /// If the commit fails, e.g. while another connection reads the file,
+    /// the changes are rolled back.
+    pub fn commit(mut self) -> Result<(), NormalError> {
+        self.conn
+            .conn()
+            .execute("RELEASE normal_transaction;")
+            .map_err(|e| NormalError::sqlite("cannot commit transaction", e))?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Discard the changes since the transaction began.
+    pub fn rollback(mut self) -> Result<(), NormalError> {
+        self.finished = true;
+        self.undo()
+    }
+
+    fn undo(&self) -> Result<(), NormalError> {
+        let conn = self.conn.conn();
+        conn.execute("ROLLBACK TO normal_transaction;")
+            .map_err(|e| NormalError::sqlite("cannot roll back transaction", e))?;
+        // Releasing the outermost savepoint commits, which fails while
+        // another connection reads the file; end the transaction instead.
+        conn.execute("RELEASE normal_transaction;")
+            .or_else(|_| conn.execute("ROLLBACK;"))
+            .map_err(|e| NormalError::sqlite("cannot roll back transaction", e))
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.undo(); // nothing to report to from a destructor.
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./transaction_test.rs"]
+mod transaction_test;
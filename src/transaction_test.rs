@@ -0,0 +1,85 @@
+use super::*;
+use parking_lot::ReentrantMutex;
+use std::sync::Arc;
+use std::thread;
+
+fn new_connection() -> ReentrantMutex<CachedConnection> {
+    let conn = sqlite::open(":memory:").unwrap();
+    conn.execute("CREATE TABLE xs (x INTEGER)").unwrap();
+    ReentrantMutex::new(CachedConnection::new(conn).unwrap())
+}
+
+fn insert(conn: &ReentrantMutex<CachedConnection>, x: i64) {
+    conn.lock()
+        .conn()
+        .execute(format!("INSERT INTO xs VALUES ({})", x))
+        .unwrap();
+}
+
+fn count(conn: &ReentrantMutex<CachedConnection>) -> i64 {
+    let conn = conn.lock();
+    let mut statement = conn.conn().prepare("SELECT COUNT(*) FROM xs").unwrap();
+    statement.next().unwrap();
+    statement.read::<i64>(0).unwrap()
+}
+
+/// Committed changes persist.
+#[test]
+fn it_commits() {
+    let conn = new_connection();
+    let tx = Transaction::begin(conn.lock()).unwrap();
+    insert(&conn, 1);
+    tx.commit().unwrap();
+    assert_eq!(count(&conn), 1);
+}
+
+/// Rolled back changes are discarded.
+#[test]
+fn it_rolls_back() {
+    let conn = new_connection();
+    let tx = Transaction::begin(conn.lock()).unwrap();
+    insert(&conn, 1);
+    tx.rollback().unwrap();
+    assert_eq!(count(&conn), 0);
+}
+
+/// Dropping an unfinished transaction rolls it back.
+#[test]
+fn it_rolls_back_on_drop() {
+    let conn = new_connection();
+    {
+        let _tx = Transaction::begin(conn.lock()).unwrap();
+        insert(&conn, 1);
+    }
+    assert_eq!(count(&conn), 0);
+}
+
+/// Inner transactions roll back without affecting outer ones.
+#[test]
+fn it_nests() {
+    let conn = new_connection();
+    let outer = Transaction::begin(conn.lock()).unwrap();
+    insert(&conn, 1);
+    {
+        let inner = Transaction::begin(conn.lock()).unwrap();
+        insert(&conn, 2);
+        inner.rollback().unwrap();
+    }
+    outer.commit().unwrap();
+    assert_eq!(count(&conn), 1);
+}
+
+/// Other threads wait for an open transaction to finish.
+#[test]
+fn it_excludes_other_threads() {
+    let conn = Arc::new(new_connection());
+    let tx = Transaction::begin(conn.lock()).unwrap();
+    let writer = {
+        let conn = conn.clone();
+        thread::spawn(move || insert(&conn, 2))
+    };
+    insert(&conn, 1);
+    tx.rollback().unwrap();
+    writer.join().unwrap();
+    assert_eq!(count(&conn), 1);
+}